@@ -2,13 +2,114 @@ use crate::schema;
 use crate::parser;
 use crate::parser::Type;
 use crate::schema::NamedTypeKind;
-use std::io::Write;
-use std::fs;
+use crate::visit::Visitor;
+use crate::printer::{Printer, PrintOptions};
+use crate::document::DocumentSet;
+use crate::error::{Diagnostic, Location};
+use std::path::Path;
 use std::collections::{HashMap, HashSet};
 
+//names of the fragments spread anywhere in a selection set
+struct FragmentSpreads<'a>(HashSet<&'a str>);
+
+impl<'a> Visitor<'a> for FragmentSpreads<'a> {
+    fn visit_fragment_spread(&mut self, spread: &parser::FragmentSpread<'a>) {
+        self.0.insert(spread.name);
+    }
+}
+
+//a field of a selection set, the fields of inline fragments without a type condition are merged into the enclosing one
+struct Selection<'f, 'a> {
+    field: &'f parser::Field<'a>,
+    //merged from an inline fragment behind @include or @skip, or having one of them itself, so it can be missing
+    guarded: bool,
+    //the field with the sub-selections of every field of the same response key, when it is selected more than once
+    merged: Option<parser::Field<'a>>,
+}
+
+impl<'f, 'a> Selection<'f, 'a> {
+    fn field(&self) -> &parser::Field<'a> {
+        self.merged.as_ref().unwrap_or(self.field)
+    }
+}
+
+//pairs of fields selected under the same response key that can't be merged into one
+type Conflicts<'f, 'a> = Vec<(&'f parser::PlainField<'a>, &'f parser::PlainField<'a>)>;
+
+fn selections<'f, 'a>(fields: &'f Vec<parser::Field<'a>>) -> Vec<Selection<'f, 'a>> {
+    let mut result = vec![];
+    collect_selections(fields, false, &mut result, &mut vec![]);
+    result
+}
+
+fn value_string(value: &parser::Value) -> String {
+    let mut printer = Printer::new(PrintOptions::default());
+    printer.print_value(value);
+    printer.finish()
+}
+
+//the order of the arguments doesn't matter
+fn same_arguments(args: &[parser::Argument], other: &[parser::Argument]) -> bool {
+    args.len() == other.len() && args.iter().all(|arg| {
+        other.iter().any(|other| other.name == arg.name && value_string(&other.value) == value_string(&arg.value))
+    })
+}
+
+//the sub-selection of a guarded field is wrapped in an inline fragment that is guarded too,
+//so its fields stay optional when merged with a field that is always there
+fn merged_fields<'a>(field: &parser::PlainField<'a>, guarded: bool) -> Vec<parser::Field<'a>> {
+    if !guarded || field.fields.is_empty() { return field.fields.clone() }
+
+    //only the name of the directive is looked at
+    let include = parser::Directive{ name: "include", args: vec![] };
+    vec![parser::Field::InlineFragment(parser::InlineFragment{ on: None, location: field.location, directives: vec![include], fields: field.fields.clone() })]
+}
+
+fn collect_selections<'f, 'a>(fields: &'f Vec<parser::Field<'a>>, guarded: bool, result: &mut Vec<Selection<'f, 'a>>, conflicts: &mut Conflicts<'f, 'a>) {
+    for field in fields {
+        match field {
+            parser::Field::InlineFragment(inline) if inline.on.is_none() => {
+                collect_selections(&inline.fields, guarded || parser::is_conditional(&inline.directives), result, conflicts)
+            },
+            parser::Field::PlainField(plain) => {
+                let guarded = guarded || parser::is_conditional(&plain.directives);
+                //a field selected again is declared once, it is only missing when every selection of it is guarded
+                let existing = result.iter_mut().find(|selection| match selection.field {
+                    parser::Field::PlainField(other) => other.response_key() == plain.response_key(),
+                    _ => false
+                });
+                match existing {
+                    Some(existing) => {
+                        if let parser::Field::PlainField(first) = existing.field {
+                            if first.name != plain.name || !same_arguments(&first.args, &plain.args) { conflicts.push((first, plain)) }
+                        }
+                        if existing.merged.is_none() {
+                            if let parser::Field::PlainField(first) = existing.field {
+                                let fields = merged_fields(first, existing.guarded);
+                                existing.merged = Some(parser::Field::PlainField(parser::PlainField{ fields, ..first.clone() }));
+                            }
+                        }
+                        if let Some(parser::Field::PlainField(merged)) = &mut existing.merged {
+                            merged.fields.append(&mut merged_fields(plain, guarded));
+                        }
+                        existing.guarded &= guarded;
+                    },
+                    None => result.push(Selection{ field, guarded, merged: None }),
+                }
+            },
+            _ => result.push(Selection{ field, guarded, merged: None }),
+        }
+    }
+}
+
 pub struct Codegen<'a> {
-    fragments_on: &'a HashMap<String, String>,
     schema: &'a schema::Schema,
+    //to look up the fragments spread in a selection set
+    documents: &'a DocumentSet<'a>,
+    //of the document being generated
+    path: &'a Path,
+    //names the type of an anonymous operation
+    document_name: String,
     src: String,
     indent: usize,
 }
@@ -42,62 +143,92 @@ impl<'a> Codegen<'a> {
 
     fn newline(&mut self) {
         self.src += "\n";
-        for i in 0..self.indent {
+        for _ in 0..self.indent {
             self.src += "    ";
         }
     }
 
+    fn gen_doc_comments(&mut self, comments: &Vec<&str>) {
+        for comment in comments {
+            self.newline();
+            self.src += "///";
+            self.src += comment;
+        }
+    }
+
     pub fn swift_name(s: &str) -> String {
         let mut c = s.chars();
         c.next().unwrap().to_uppercase().chain(c).collect()
     }
 
-    fn write_type_non_nullable(&mut self, of_type: &parser::Type, fields: &Vec<parser::Field>, nest_type: &str) {
+    //the types come from the schema, an inconsistent one is reported instead of generating a broken type
+    fn write_type_non_nullable(&mut self, of_type: &parser::Type, fields: &Vec<parser::Field>, nest_type: &str) -> Result<(), Diagnostic> {
         match of_type {
-            parser::Type::NonNull(_) => panic!("unexpected non null!"),
+            parser::Type::NonNull(_) => return Err(Diagnostic::error("C010", "Non null type wrapped in another non null type")),
             parser::Type::String => self.src += "String",
             parser::Type::Int => self.src += "Int",
             parser::Type::Float => self.src += "Float",
             parser::Type::Bool => self.src += "Bool",
             parser::Type::Input(name) => {
-                let kind = &self.schema.get(name).unwrap().kind;
-                if *kind == NamedTypeKind::InputObject || *kind == NamedTypeKind::Scalar {
-                    return self.src += name;
-                }
-                if let Some(frag) = self.sole_fragment(fields) {
-                    return self.src += &Self::swift_name(frag);
+                let kind = &self.schema.get(name).ok_or_else(|| Diagnostic::error("C002", format!("Unknown type {}", name)))?.kind;
+                if *kind == NamedTypeKind::InputObject || *kind == NamedTypeKind::Scalar || *kind == NamedTypeKind::Enum {
+                    self.src += name;
+                } else if let Some(frag) = self.sole_fragment(fields) {
+                    self.src += &Self::swift_name(frag);
+                } else {
+                    self.src += nest_type
                 }
-                self.src += nest_type
             },
             parser::Type::Array(elem) => {
                 self.src += "[";
-                self.write_type(elem, fields, nest_type);
+                self.write_type(elem, fields, nest_type)?;
                 self.src += "]";
             }
         }
+        Ok(())
     }
 
-    fn write_type(&mut self, of_type: &parser::Type, fields: &Vec<parser::Field>, nest_type: &str) {
+    fn write_type(&mut self, of_type: &parser::Type, fields: &Vec<parser::Field>, nest_type: &str) -> Result<(), Diagnostic> {
         match of_type {
             parser::Type::NonNull(of_type) => self.write_type_non_nullable(of_type.as_ref(), fields, nest_type),
             _ => {
-                self.write_type_non_nullable(of_type, fields, nest_type);
+                self.write_type_non_nullable(of_type, fields, nest_type)?;
                 self.src += "?";
+                Ok(())
             }
         }
     }
 
+    fn nullable(of_type: &parser::Type) -> &parser::Type {
+        match of_type {
+            parser::Type::NonNull(of_type) => of_type.as_ref(),
+            _ => of_type
+        }
+    }
+
+    //a field behind @include or @skip is optional even if the schema says it is non null
+    fn write_field_type(&mut self, of_type: &parser::Type, conditional: bool, fields: &Vec<parser::Field>, nest_type: &str) -> Result<(), Diagnostic> {
+        if conditional {
+            self.write_type(Self::nullable(of_type), fields, nest_type)
+        } else {
+            self.write_type(of_type, fields, nest_type)
+        }
+    }
+
 
     fn sole_fragment(&self, fields: &Vec<parser::Field<'a>>) -> Option<&'a str> {
-        if fields.len() == 1 {
-            if let parser::Field::Fragment(frag) = fields[0] { return Some(frag); }
+        let selections = selections(fields);
+        if selections.len() == 1 && !selections[0].guarded {
+            if let parser::Field::Fragment(frag) = selections[0].field() {
+                if !parser::is_conditional(&frag.directives) { return Some(frag.name); }
+            }
         }
         None
     }
 
     fn has_only_fragments(&self, fields: &Vec<parser::Field>) -> bool {
-        for field in fields {
-            if let parser::Field::PlainField(_) = field { return false }
+        for selection in selections(fields) {
+            if let parser::Field::PlainField(_) = selection.field() { return false }
         }
         true
     }
@@ -114,7 +245,7 @@ impl<'a> Codegen<'a> {
     }
 
     fn should_gen_nested_types(&self, fields: &Vec<parser::Field<'a>>) -> bool {
-        fields.len() > 0 && self.sole_fragment(&fields).is_none()
+        !fields.is_empty() && self.sole_fragment(fields).is_none()
     }
     /*
     fn inline_frag_type(&mut self) {
@@ -127,17 +258,20 @@ impl<'a> Codegen<'a> {
     }*/
 
 
-    fn gen_custom_interface_decoding(&mut self, object_type: &schema::NamedType, fields: &Vec<parser::Field>) {
+    fn gen_custom_interface_decoding(&mut self, object_type: &schema::NamedType, fields: &Vec<parser::Field>) -> Result<(), Diagnostic> {
         self.newline();
         self.src += "enum CodingKeys : String, CodingKey";
         self.opening_brace();
         self.newline();
         self.src += "case __typename";
 
-        for field in fields {
-            if let parser::Field::PlainField(field) = field {
-                self.src += ", ";
-                self.src += field.name;
+        for selection in selections(fields) {
+            match selection.field() {
+                parser::Field::PlainField(field) if field.response_key() != "__typename" => {
+                    self.src += ", ";
+                    self.src += field.response_key();
+                },
+                _ => {},
             }
         }
 
@@ -148,15 +282,22 @@ impl<'a> Codegen<'a> {
         self.opening_brace();
         self.newline();
         self.src += "let container = try decoder.container(keyedBy: CodingKeys.self)";
-        for field in fields {
-            if let parser::Field::PlainField(field) = field {
+        for selection in selections(fields) {
+            if let parser::Field::PlainField(field) = selection.field() {
                 self.newline();
                 self.src += "self.";
-                self.src += field.name;
-                self.src += " = try container.decode(";
-                self.write_type(&object_type.fields[field.name].of_type, &field.fields, field.name);
+                self.src += field.response_key();
+                let nest_type = Self::swift_name(field.response_key());
+                let of_type = object_type.field_type(field.name)?;
+                if selection.guarded {
+                    self.src += " = try container.decodeIfPresent(";
+                    self.write_type_non_nullable(Self::nullable(&of_type), &field.fields, &nest_type)?;
+                } else {
+                    self.src += " = try container.decode(";
+                    self.write_type(&of_type, &field.fields, &nest_type)?;
+                }
                 self.src += ".self, forKey: .";
-                self.src += field.name;
+                self.src += field.response_key();
                 self.src += ")";
                 //id = try container.decode(String.self, forKey: .id)
                 //mfgInfo = try MfgInfo(from: decoder)
@@ -165,34 +306,79 @@ impl<'a> Codegen<'a> {
         self.newline();
         self.src += "self.kind = try Types(from: decoder)";
         self.closing_brace();
+        Ok(())
     }
 
 
-    fn gen_enum_for_possible_types(&mut self, object_type: &schema::NamedType, name: &str, fields: &Vec<parser::Field<'a>>) {
-        let is_identifiable = object_type.fields.get("id").is_some();
+    fn is_conditional_case(selection: &Selection) -> bool {
+        selection.guarded || match selection.field() {
+            parser::Field::Fragment(frag) => parser::is_conditional(&frag.directives),
+            parser::Field::InlineFragment(inline) => parser::is_conditional(&inline.directives),
+            _ => false
+        }
+    }
+
+    //a key of the response that is only there when the fields of a conditional case were included
+    fn presence_key(&self, fields: &Vec<parser::Field<'a>>, always: &HashSet<&'a str>, spread: &mut HashSet<&'a str>) -> Option<&'a str> {
+        let documents = self.documents;
+
+        for selection in selections(fields) {
+            if selection.guarded { continue }
+
+            match selection.field() {
+                parser::Field::PlainField(field) => {
+                    let key = field.response_key();
+                    if key != "__typename" && !always.contains(key) { return Some(key) }
+                },
+                parser::Field::Fragment(frag) if !parser::is_conditional(&frag.directives) && spread.insert(frag.name) => {
+                    let key = documents.fragment(frag.name).and_then(|(_, fragment)| self.presence_key(&fragment.fields, always, spread));
+                    if key.is_some() { return key }
+                },
+                _ => {},
+            }
+        }
+
+        None
+    }
+
+    fn gen_enum_for_possible_types(&mut self, object_type: &schema::NamedType, name: &str, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
+        //a conditional case has no value when its fragment was left out, so there is no id to return
+        let selections = selections(fields);
+        let is_identifiable = object_type.fields.contains_key("id") && !selections.iter().any(Self::is_conditional_case);
         self.newline();
         self.gen_type_def("enum", name, is_identifiable);
 
+        //fields selected next to the cases, they are in the response whether a case was included or not
+        let always = selections.iter().filter_map(|selection| match selection.field() {
+            parser::Field::PlainField(field) if !selection.guarded => Some(field.response_key()),
+            _ => None,
+        }).collect();
+        let documents = self.documents;
+
         let mut cases = vec![];
 
-        for field in fields {
-            let (name, of_type) = match field {
-                parser::Field::Fragment(name) => (*name, *name), //todo get named on
+        for selection in &selections {
+            let (name, of_type, case_fields) = match selection.field() {
+                parser::Field::Fragment(frag) => (frag.name, frag.name, documents.fragment(frag.name).map(|(_, fragment)| &fragment.fields)), //todo get named on
                 parser::Field::InlineFragment(inline) => {
-                    let name : &str = &self.schema.get_named(&inline.on).name;
+                    let on = match &inline.on { Some(on) => on, None => continue };
+                    let name : &str = &self.schema.get_named(on)?.name;
                     if let Some(frag) = self.sole_fragment(&inline.fields) {
-                        (name, frag)
+                        (name, frag, Some(&inline.fields))
                     } else {
-                        (name, name)
+                        (name, name, Some(&inline.fields))
                     }
                 },
                 _ => {continue},
             };
+            let conditional = Self::is_conditional_case(selection);
+            //a case that was left out is told apart from one that fails to decode by a key only it selects
+            let key = if conditional { case_fields.and_then(|fields| self.presence_key(fields, &always, &mut HashSet::new())) } else { None };
 
             self.newline();
             let type_name = Self::swift_name(of_type);
-            self.src += &format!("case As{}({})", name, &type_name);
-            cases.push((name, type_name));
+            self.src += &format!("case As{}({}{})", name, &type_name, if conditional { "?" } else { "" });
+            cases.push((name, type_name, key));
         }
 
         if is_identifiable {
@@ -203,7 +389,7 @@ impl<'a> Codegen<'a> {
             self.src += "switch self";
             self.opening_brace();
 
-            for (name, of_type) in &cases {
+            for (name, _, _) in &cases {
                 self.newline();
                 self.src += "case let .As";
                 self.src += name;
@@ -213,24 +399,45 @@ impl<'a> Codegen<'a> {
             self.closing_brace();
         }
 
+        let mut keys = vec![];
+        for (_, _, key) in &cases {
+            if let Some(key) = key {
+                if !keys.contains(key) { keys.push(*key) }
+            }
+        }
+        if !keys.is_empty() {
+            self.newline();
+            self.src += "enum CaseKeys : String, CodingKey";
+            self.opening_brace();
+            self.newline();
+            self.src += "case __typename";
+            for key in &keys {
+                self.src += ", ";
+                self.src += key;
+            }
+            self.closing_brace();
+        }
+
         self.newline();
         self.src += "init(from decoder: Decoder) throws";
         self.opening_brace();
         self.newline();
-        self.src += "let container = try decoder.container(keyedBy: TypenameKeys.self)";
+        self.src += if !keys.is_empty() { "let container = try decoder.container(keyedBy: CaseKeys.self)" } else { "let container = try decoder.container(keyedBy: TypenameKeys.self)" };
         self.newline();
         self.src += "switch try container.decode(String.self, forKey: .__typename)";
         self.opening_brace();
 
-        for (name, of_type) in &cases {
+        for (name, of_type, key) in &cases {
             self.newline();
             self.src += "case \"";
             self.src += name;
             self.src += "\" : self = .As";
             self.src += name;
-            self.src += "(try ";
-            self.src += &of_type;
-            self.src += "(from: decoder))";
+            //only a case that is missing from the response is nil, a case that is there but doesn't decode is an error
+            match key {
+                Some(key) => self.src += &format!("(container.contains(.{}) ? try {}(from: decoder) : nil)", key, of_type),
+                None => self.src += &format!("(try {}(from: decoder))", of_type),
+            }
         }
         self.newline();
         self.src += "default: throw UnknownTypename()";
@@ -241,6 +448,7 @@ impl<'a> Codegen<'a> {
 
         self.closing_brace();
         self.newline();
+        Ok(())
     }
 
     fn gen_type_def(&mut self, kind: &str, name: &str, is_identifiable: bool) {
@@ -255,9 +463,9 @@ impl<'a> Codegen<'a> {
     }
 
     fn has_id_field(&self, fields: &Vec<parser::Field<'a>>) -> bool {
-        for field in fields {
-            match field {
-                parser::Field::PlainField(field) if field.name == "id" => return true,
+        for selection in selections(fields) {
+            match selection.field() {
+                parser::Field::PlainField(field) if field.response_key() == "id" && !selection.guarded => return true,
                 _ => {}
             }
         }
@@ -266,80 +474,83 @@ impl<'a> Codegen<'a> {
     }
 
 
-    fn gen_type_for_fields(&mut self, object_type: &schema::NamedType, is_interface: bool, fields: &Vec<parser::Field<'a>>) {
-        for field in fields {
-            match field {
-                parser::Field::PlainField(field) => {
-                    if self.should_gen_nested_types(&field.fields) {
-                        let named = self.schema.get_type_of_field(object_type, field.name);
+    fn gen_type_for_fields(&mut self, object_type: &schema::NamedType, is_interface: bool, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
+        for selection in selections(fields) {
+            match selection.field() {
+                parser::Field::PlainField(field) if self.should_gen_nested_types(&field.fields) => {
+                    let named = self.schema.get_type_of_field(object_type, field.name)?;
 
-                        self.gen_type_for(named, field.name, &field.fields);
-                    }
+                    self.gen_type_for(named, field.response_key(), &field.fields)?;
                 },
                 parser::Field::InlineFragment(inline) => {
-                    if is_interface { return }
+                    if is_interface { return Ok(()) }
 
-                    let on_type = self.schema.get_named(&inline.on);
-                    self.gen_type_for(on_type, &on_type.name, &inline.fields);
+                    let on_type = match &inline.on { Some(on) => self.schema.get_named(on)?, None => continue };
+                    self.gen_type_for(on_type, &on_type.name, &inline.fields)?;
                 }
                 _ => {},
             }
         }
+        Ok(())
     }
 
-    fn gen_type_for(&mut self, object_type: &schema::NamedType, name: &'a str, fields: &Vec<parser::Field<'a>>) -> &'a str {
+    fn gen_type_for(&mut self, object_type: &schema::NamedType, name: &'a str, fields: &Vec<parser::Field<'a>>) -> Result<&'a str, Diagnostic> {
         match self.anaylze(object_type, fields) {
-            TypeCase::SoleFragment(name) => return name,
+            TypeCase::SoleFragment(name) => return Ok(name),
             TypeCase::InterfaceOnlyFragments => {
                 self.newline();
-                self.gen_enum_for_possible_types(object_type, name, fields);
+                self.gen_enum_for_possible_types(object_type, name, fields)?;
             }
             TypeCase::Interface => {
                 self.newline();
                 self.gen_type_def("struct", name, self.has_id_field(fields));
-                self.gen_enum_for_possible_types(object_type, "Types", fields);
-                self.gen_type_for_fields(object_type, true, fields);
-                self.gen_fields(object_type, fields);
-                self.gen_custom_interface_decoding(object_type, fields);
+                self.gen_enum_for_possible_types(object_type, "Types", fields)?;
+                self.gen_type_for_fields(object_type, true, fields)?;
+                self.gen_fields(object_type, fields)?;
+                self.gen_custom_interface_decoding(object_type, fields)?;
                 self.closing_brace();
             }
 
             TypeCase::Regular => {
                 self.newline();
                 self.gen_type_def("struct", name, self.has_id_field(fields));
-                self.gen_type_for_fields(object_type, false, fields);
-                self.gen_fields(object_type, fields);
+                self.gen_type_for_fields(object_type, false, fields)?;
+                self.gen_fields(object_type, fields)?;
                 self.closing_brace();
             },
         }
 
-        name
+        Ok(name)
     }
 
-    fn gen_fields(&mut self, object_type: &schema::NamedType, fields: &Vec<parser::Field<'a>>) {
+    fn gen_fields(&mut self, object_type: &schema::NamedType, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
         let is_interface = object_type.kind == NamedTypeKind::Interface;
 
-        for field in fields {
-            match field {
+        for selection in selections(fields) {
+            match selection.field() {
                 parser::Field::PlainField(field) => {
+                    self.gen_doc_comments(&field.comments);
                     self.newline();
-                    let schema_field = &object_type.fields[field.name];
+                    let of_type = object_type.field_type(field.name)?;
                     self.src += "var ";
-                    self.src += field.name;
+                    self.src += field.response_key();
                     self.src += " : ";
 
-                    let nest_type = Self::swift_name(field.name);
-                    self.write_type(&schema_field.of_type, &field.fields, &nest_type);
+                    let nest_type = Self::swift_name(field.response_key());
+                    let conditional = selection.guarded;
+                    self.write_field_type(&of_type, conditional, &field.fields, &nest_type)?;
                 },
                 parser::Field::InlineFragment(_frag) => {
 
                 },
                 parser::Field::Fragment(frag) => {
                     if !is_interface {
+                        self.newline();
                         self.src += "var ";
-                        self.src += frag;
+                        self.src += frag.name;
                         self.src += " : ";
-                        self.src += &Self::swift_name(frag);
+                        self.src += &Self::swift_name(frag.name);
+                        if selection.guarded || parser::is_conditional(&frag.directives) { self.src += "?" }
                     }
                 },
             }
@@ -349,118 +560,134 @@ impl<'a> Codegen<'a> {
             self.newline();
             self.src += "var kind : Types"
         }
+        Ok(())
     }
 
-    fn gen_args(&mut self, args: &Vec<parser::ArgumentDef<'a>>) {
+    fn gen_args(&mut self, args: &Vec<parser::ArgumentDef<'a>>) -> Result<(), Diagnostic> {
         for arg in args {
             self.src += "var ";
             self.src += arg.name;
             self.src += " : ";
-            self.write_type(&arg.kind, &vec![], "");
+            self.write_type(&arg.kind, &vec![], "")?;
+            //with a default the memberwise initializer lets callers leave the argument out
+            if let Some(default) = &arg.default {
+                let literal = self.swift_literal(default, &arg.kind).ok_or_else(|| {
+                    Diagnostic::error("C013", format!("Default value of ${} can't be written as a Swift literal", arg.name))
+                        .with_primary(self.path, Some(arg.location), "default value")
+                })?;
+                self.src += " = ";
+                self.src += &literal;
+            }
             self.newline();
         }
+        Ok(())
     }
 
-    fn gen_ql_value(&mut self, value: &parser::Value) {
-        match value {
-            parser::Value::Bool(b)  => if *b { self.src += "true" } else { self.src += "false " },
-            parser::Value::String(s) => {
-                self.src += "\"";
-                self.src += s;
-                self.src += "\"";
-            },
-            parser::Value::Int(i) => self.src += &format!("{} ", i),
-            parser::Value::Variable(name) => {
-                self.src += "$";
-                self.src += name;
+    fn swift_string(s: &str) -> String {
+        let mut literal = String::from("\"");
+        for c in s.chars() {
+            match c {
+                '"' => literal += "\\\"",
+                '\\' => literal += "\\\\",
+                '\n' => literal += "\\n",
+                '\r' => literal += "\\r",
+                '\t' => literal += "\\t",
+                c if c.is_control() => literal += &format!("\\u{{{:x}}}", c as u32),
+                c => literal.push(c),
             }
         }
+        literal += "\"";
+        literal
     }
 
-    fn gen_ql_type(&mut self, of_type: &parser::Type) {
-        match of_type {
-            Type::String => self.src += "String",
-            Type::Float => self.src += "Float",
-            Type::Bool => self.src += "Bool",
-            Type::Int => self.src += "Int",
-            Type::NonNull(elem) => {
-                self.gen_ql_type(elem);
-                self.src += "!";
-            }
-            Type::Array(elem) => {
-                self.src += "[";
-                self.gen_ql_type(elem);
-                self.src += "]";
-            }
-            Type::Input(input) => self.src += input
-        }
-    }
+    //None for a variable, or an input object the schema doesn't have the fields of
+    fn swift_literal(&self, value: &parser::Value, of_type: &parser::Type) -> Option<String> {
+        match value {
+            parser::Value::Bool(b) => Some(b.to_string()),
+            parser::Value::String(s) | parser::Value::BlockString(s) => Some(Self::swift_string(s)),
+            parser::Value::Int(i) => Some(i.to_string()),
+            parser::Value::Float(f) => Some(format!("{:?}", f)),
+            parser::Value::Null => Some("nil".to_string()),
+            parser::Value::Enum(name) => Some(format!(".{}", name)),
+            parser::Value::List(values) => {
+                let of_type = match Self::nullable(of_type) {
+                    parser::Type::Array(elem) => elem.as_ref(),
+                    of_type => of_type,
+                };
+                let values : Option<Vec<String>> = values.iter().map(|value| self.swift_literal(value, of_type)).collect();
+                Some(format!("[{}]", values?.join(", ")))
+            },
+            parser::Value::Object(fields) => {
+                let name = match Self::nullable(of_type) {
+                    parser::Type::Input(name) => name,
+                    _ => return None,
+                };
+                //the memberwise initializer takes the fields in the order of the schema, the ones left out are optional
+                let mut args = vec![];
+                for input in &self.schema.get(name)?.input_fields {
+                    if let Some(field) = fields.iter().find(|field| field.name == input.name) {
+                        args.push(format!("{}: {}", input.name, self.swift_literal(&field.value, &input.of_type)?));
+                    }
+                }
+                if args.len() != fields.len() { return None }
 
-    fn comma_seperated<F: Fn(&mut Codegen<'a>, &T), T>(&mut self, vec: &Vec<T>, f: F) {
-        for (i, arg) in vec.iter().enumerate() { //todo create helper which checks if last
-            f(self, arg);
-            if i + 1 < vec.len() {
-                self.src += ", ";
-            }
+                Some(format!("{}({})", name, args.join(", ")))
+            },
+            parser::Value::Variable(_) => None,
         }
     }
 
-    fn gen_ql_fields(&mut self, object_type: &schema::NamedType, fields: &Vec<parser::Field<'a>>)  {
-        if fields.len() == 0 { return }
+    //codegen decodes interfaces by their __typename, so it is added to their selection sets
+    fn gen_ql_fields(&self, printer: &mut Printer, object_type: &schema::NamedType, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
+        self.gen_ql_selection_set(printer, object_type, object_type.kind == NamedTypeKind::Interface, fields)
+    }
 
+    fn gen_ql_selection_set(&self, printer: &mut Printer, object_type: &schema::NamedType, typename: bool, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
+        if fields.is_empty() { return Ok(()) }
 
-        self.opening_brace();
+        printer.open_block();
 
-        if object_type.kind == NamedTypeKind::Interface {
-            self.newline();
-            self.src += "__typename";
+        if typename {
+            printer.newline();
+            printer.write("__typename");
         }
 
         for field in fields {
-            self.newline();
+            printer.newline();
             match field {
                 parser::Field::PlainField(plain_field) => {
-                    self.src += plain_field.name;
-                    if plain_field.args.len() > 0 {
-                        self.src += "(";
-                        self.comma_seperated(&plain_field.args, |codegen, arg| { //todo create helper which checks if last
-                            codegen.src += arg.name;
-                            codegen.src += " : ";
-                            codegen.gen_ql_value(&arg.value);
-                        });
-                        self.src += ")";
-                    }
-                    if plain_field.fields.len() > 0 {
-                        self.gen_ql_fields(self.schema.get_type_of_field(object_type, plain_field.name), &plain_field.fields);
+                    printer.print_plain_field_head(plain_field);
+                    if !plain_field.fields.is_empty() {
+                        self.gen_ql_fields(printer, self.schema.get_type_of_field(object_type, plain_field.name)?, &plain_field.fields)?;
                     }
                 },
-                parser::Field::Fragment(frag) => {
-                    self.src += "...";
-                    self.src += frag;
-                },
+                parser::Field::Fragment(frag) => printer.print_fragment_spread(frag),
                 parser::Field::InlineFragment(inline) => {
-                    self.src += "... on ";
-                    self.gen_ql_type(&inline.on);
-                    self.gen_ql_fields(self.schema.get_named(&inline.on), &inline.fields);
+                    printer.print_inline_fragment_head(inline);
+                    match &inline.on {
+                        Some(on) => self.gen_ql_fields(printer, self.schema.get_named(on)?, &inline.fields)?,
+                        //the enclosing selection set already has the __typename
+                        None => self.gen_ql_selection_set(printer, object_type, false, &inline.fields)?,
+                    }
                 },
             }
         }
-        self.closing_brace();
+        printer.close_block();
+        Ok(())
     }
 
-    fn find_fragments(fragments: &mut HashSet<&'a str>, fields: &Vec<parser::Field<'a>>) {
-        for field in fields {
-            match field {
-                parser::Field::PlainField(field) => Self::find_fragments(fragments, &field.fields),
-                parser::Field::InlineFragment(inline) => Self::find_fragments(fragments, &inline.fields),
-                parser::Field::Fragment(on) => { fragments.insert(on); },
-            }
+    //the printed graphql indented to the current swift code
+    fn gen_ql_lines(&mut self, printer: Printer) {
+        for line in printer.finish().lines() {
+            self.newline();
+            self.src += line;
         }
     }
 
     fn gen_dependent_fragments(&mut self, fields: &Vec<parser::Field<'a>>) {
-        let mut fragments = HashSet::new();
-        Self::find_fragments(&mut fragments, fields);
+        let mut spreads = FragmentSpreads(HashSet::new());
+        spreads.visit_selection_set(fields);
+        let fragments = spreads.0;
 
         self.src += "[";
 
@@ -474,84 +701,102 @@ impl<'a> Codegen<'a> {
         self.src += "]";
     }
 
-    fn gen_ql_args(&mut self, args: &Vec<parser::ArgumentDef<'a>>) {
-        if args.len() > 0 {
-            self.src += "(";
-            self.comma_seperated(args,  |codegen, arg| {
-                codegen.src += "$";
-                codegen.src += arg.name;
-                codegen.src += " : ";
-                codegen.gen_ql_type(&arg.kind);
-            });
-            self.src += ")";
-        }
-    }
-
-    fn gen_ql(&mut self, kind: &str, base: &schema::NamedType, name: &str, args: &Vec<parser::ArgumentDef<'a>>, fields: &Vec<parser::Field<'a>>) {
+    fn gen_ql(&mut self, kind: &str, base: &schema::NamedType, name: Option<&str>, args: &Vec<parser::ArgumentDef<'a>>, directives: &Vec<parser::Directive<'a>>, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
         self.src += "static let fragments : [String] = ";
         self.gen_dependent_fragments(fields);
 
         self.newline();
 
-        self.src += "static let graphql = \"\"\"";
-        self.newline();
-        self.src += kind;
-        self.src += " ";
-        self.src += name;
-
-        self.gen_ql_args(&args);
-        self.gen_ql_fields(base, &fields);
+        //raw string so escapes in GraphQL strings reach the server unchanged
+        self.src += "static let graphql = #\"\"\"";
+        let mut printer = Printer::new(PrintOptions{ indent: 4, ..PrintOptions::default() });
+        printer.write(kind);
+        if let Some(name) = name {
+            printer.write(" ");
+            printer.write(name);
+        }
+        printer.print_variable_definitions(args);
+        printer.print_directives(directives);
+        self.gen_ql_fields(&mut printer, base, fields)?;
+        self.gen_ql_lines(printer);
         self.newline();
-        self.src += "\"\"\"";
+        self.src += "\"\"\"#";
         self.newline();
+        Ok(())
     }
 
-    fn gen_api_for(&mut self, kind: &str, base: &schema::NamedType, name: &str, args: &Vec<parser::ArgumentDef<'a>>, fields: &Vec<parser::Field<'a>>) {
-        self.newline();
+    fn gen_api_for(&mut self, kind: &str, base: &schema::NamedType, name: Option<&str>, args: &Vec<parser::ArgumentDef<'a>>, directives: &Vec<parser::Directive<'a>>, fields: &Vec<parser::Field<'a>>) -> Result<(), Diagnostic> {
         self.newline();
 
         let kind_upper = Self::swift_name(kind);
 
-        self.src += &format!("struct {}{} : Encodable, GraphQL{}", Self::swift_name(name), &kind_upper, &kind_upper);
+        let type_name = Self::swift_name(name.unwrap_or(&self.document_name));
+        self.src += &format!("struct {}{} : Encodable, GraphQL{}", type_name, &kind_upper, &kind_upper);
         self.opening_brace();
         self.newline();
-        self.gen_ql(kind, base, &name, &args, &fields);
+        self.gen_ql(kind, base, name, args, directives, fields)?;
         self.newline();
-        self.gen_args(&args);
+        self.gen_args(args)?;
 
-        self.gen_type_for(base, "Data", &fields);
+        self.gen_type_for(base, "Data", fields)?;
         self.closing_brace();
+        Ok(())
     }
 
-    fn gen_queries(&mut self, queries: &Vec<parser::Query<'a>>) {
-        let schema = self.schema.query_root().unwrap();
-
+    fn gen_queries(&mut self, path: &Path, queries: &Vec<parser::Query<'a>>) -> Result<(), Diagnostic> {
         for query in queries {
-            self.gen_api_for("query", schema, &query.name, &query.args, &query.fields);
+            let schema = self.schema.query_root().ok_or_else(|| missing_root("query", path, query.location))?;
+            self.newline();
+            self.gen_doc_comments(&query.comments);
+            self.gen_api_for("query", schema, query.name, &query.args, &query.directives, &query.fields)
+                .map_err(|error| found_in(error, path, query.location, "query"))?;
         }
+        Ok(())
     }
 
-    fn gen_mutations(&mut self, mutations: &Vec<parser::Mutation<'a>>) {
-        let schema = self.schema.mutation_root().unwrap();
-
+    fn gen_mutations(&mut self, path: &Path, mutations: &Vec<parser::Mutation<'a>>) -> Result<(), Diagnostic> {
         for query in mutations {
-            self.gen_api_for("mutation", schema, &query.name, &query.args, &query.fields);
+            let schema = self.schema.mutation_root().ok_or_else(|| missing_root("mutation", path, query.location))?;
+            self.newline();
+            self.gen_doc_comments(&query.comments);
+            self.gen_api_for("mutation", schema, query.name, &query.args, &query.directives, &query.fields)
+                .map_err(|error| found_in(error, path, query.location, "mutation"))?;
         }
+        Ok(())
     }
 
-    fn gen_fragments(&mut self, fragments: &Vec<parser::Fragment<'a>>) {
+    fn gen_subscriptions(&mut self, path: &Path, subscriptions: &Vec<parser::Subscription<'a>>) -> Result<(), Diagnostic> {
+        for query in subscriptions {
+            let schema = self.schema.subscription_root().ok_or_else(|| missing_root("subscription", path, query.location))?;
+            self.newline();
+            self.gen_doc_comments(&query.comments);
+            self.gen_api_for("subscription", schema, query.name, &query.args, &query.directives, &query.fields)
+                .map_err(|error| found_in(error, path, query.location, "subscription"))?;
+        }
+        Ok(())
+    }
+
+    fn gen_document(&mut self, module: &parser::GraphQL<'a>) -> Result<(), Diagnostic> {
+        self.gen_fragments(module.path, &module.fragments)?;
+        self.gen_queries(module.path, &module.queries)?;
+        self.gen_mutations(module.path, &module.mutations)?;
+        self.gen_subscriptions(module.path, &module.subscriptions)
+    }
+
+    fn gen_fragments(&mut self, path: &Path, fragments: &Vec<parser::Fragment<'a>>) -> Result<(), Diagnostic> {
 
         for query in fragments {
             self.newline();
             self.newline();
 
-            let schema = self.schema.get_named(&query.on);
+            let schema = self.schema.get_named(&query.on).map_err(|error| found_in(error, path, query.location, "fragment"))?;
 
             //self.src += &format!("struct {} : GraphQLFragment", Self::swift_name(query.name));
             //self.opening_brace();
             //self.newline();
             //let name = Self::swift_name(query.name);
-            self.gen_type_for(schema, query.name, &query.fields);
+            self.gen_doc_comments(&query.comments);
+            self.gen_type_for(schema, query.name, &query.fields).map_err(|error| found_in(error, path, query.location, "fragment"))?;
             self.newline();
             self.src += "func init";
             self.src += &Self::swift_name(query.name);
@@ -562,31 +807,744 @@ impl<'a> Codegen<'a> {
             self.src += query.name;
             self.src += "\", fragments: ";
             self.gen_dependent_fragments(&query.fields);
-            self.src += ", graphql: \"\"\"";
+            self.src += ", graphql: #\"\"\"";
+            let mut printer = Printer::new(PrintOptions{ indent: 4, ..PrintOptions::default() });
+            printer.write("fragment ");
+            printer.write(query.name);
+            printer.write(" on ");
+            printer.write(&schema.name);
+            printer.print_variable_definitions(&query.args);
+            printer.print_directives(&query.directives);
+            self.gen_ql_fields(&mut printer, schema, &query.fields).map_err(|error| found_in(error, path, query.location, "fragment"))?;
+            self.gen_ql_lines(printer);
             self.newline();
-            self.src += "fragment ";
-            self.src += query.name;
-            self.src += " on ";
-            self.src += &schema.name;
-            self.gen_ql_args(&query.args);
-            self.gen_ql_fields(schema, &query.fields);
-            self.newline();
-            self.src += "\"\"\")";
+            self.src += "\"\"\"#)";
 
             self.closing_brace();
         }
+        Ok(())
+    }
+
+
+}
+
+//the named type a field resolves to, None for the builtin scalars
+fn named_type_name(of_type: &Type) -> Option<&str> {
+    match of_type {
+        Type::Input(name) => Some(name),
+        Type::Array(elem) | Type::NonNull(elem) => named_type_name(elem),
+        _ => None,
+    }
+}
+
+const BUILTIN_SCALARS: [&str; 5] = ["Int", "Float", "String", "Boolean", "ID"];
+
+//the name of a type that is not a list, the builtin scalars by their GraphQL name
+fn scalar_or_named(of_type: &Type) -> &str {
+    match of_type {
+        Type::Int => "Int",
+        Type::Float => "Float",
+        Type::String => "String",
+        Type::Bool => "Boolean",
+        Type::Input(name) => name,
+        Type::Array(elem) | Type::NonNull(elem) => scalar_or_named(elem),
+    }
+}
+
+fn type_string(of_type: &Type) -> String {
+    let mut printer = Printer::new(PrintOptions::default());
+    printer.print_type(of_type);
+    printer.finish()
+}
+
+//a variable can be passed where its type or a nullable version of its type is expected
+fn variable_fits(variable: &Type, expected: &Type) -> bool {
+    match (variable, expected) {
+        (Type::NonNull(variable), Type::NonNull(expected)) => variable_fits(variable, expected),
+        (Type::NonNull(variable), expected) => variable_fits(variable, expected),
+        (_, Type::NonNull(_)) => false,
+        (Type::Array(variable), Type::Array(expected)) => variable_fits(variable, expected),
+        (Type::Array(_), _) | (_, Type::Array(_)) => false,
+        (variable, expected) => scalar_or_named(variable) == scalar_or_named(expected),
+    }
+}
+
+//servers without subscriptions or mutations have no root type for them
+fn missing_root(kind: &str, path: &Path, location: Location) -> Diagnostic {
+    Diagnostic::error("C003", format!("Schema has no {} type", kind)).with_primary(path, Some(location), kind)
+}
+
+//the types codegen writes come from the schema, so its errors point at the operation or fragment being generated
+fn found_in(error: Diagnostic, path: &Path, location: Location, label: &str) -> Diagnostic {
+    if error.primary.is_some() { return error }
+    error.with_primary(path, Some(location), label)
+}
+
+//checks the documents against the schema, so codegen can assume every field and type exists
+struct Check<'s> {
+    schema: &'s schema::Schema,
+    path: &'s Path,
+    //of the operation being checked, None in fragments where they come from the operation spreading it
+    variables: Option<&'s Vec<parser::ArgumentDef<'s>>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'s> Check<'s> {
+    fn error(&mut self, code: &'static str, message: String, location: Location, label: &str) {
+        self.diagnostics.push(Diagnostic::error(code, message).with_primary(self.path, Some(location), label));
+    }
+
+    //fragments can only be spread on types with fields
+    fn type_condition(&mut self, on: &Type, location: Location) -> Option<&'s schema::NamedType> {
+        let name = scalar_or_named(on);
+        match self.schema.get(name) {
+            Some(named) if named.kind == NamedTypeKind::Object || named.kind == NamedTypeKind::Interface => return Some(named),
+            None if !BUILTIN_SCALARS.contains(&name) => self.error("C002", format!("Unknown type {}", name), location, "type condition"),
+            _ => self.error("C011", format!("Type condition on {} which is not an object or interface type", name), location, "type condition"),
+        }
+        None
+    }
+
+    //fields of the same response key are declared once, so they have to select the same field with the same arguments
+    fn merge_conflicts(&mut self, fields: &Vec<parser::Field>) {
+        let mut result = vec![];
+        let mut conflicts = vec![];
+        collect_selections(fields, false, &mut result, &mut conflicts);
+
+        for (first, second) in conflicts {
+            let message = if first.name != second.name {
+                format!("Fields {} and {} are both selected as {}", first.name, second.name, second.response_key())
+            } else {
+                format!("Field {} is selected as {} with different arguments", second.name, second.response_key())
+            };
+            self.diagnostics.push(Diagnostic::error("C012", message)
+                .with_primary(self.path, Some(second.location), "conflicting selection")
+                .with_secondary(self.path, Some(first.location), "first selected here"));
+        }
+
+        for selection in &result {
+            match selection.field() {
+                parser::Field::PlainField(plain) => self.merge_conflicts(&plain.fields),
+                parser::Field::InlineFragment(inline) => self.merge_conflicts(&inline.fields),
+                parser::Field::Fragment(_) => {},
+            }
+        }
+    }
+
+    fn root(&mut self, kind: &str, root: Option<&'s schema::NamedType>, location: Location, args: &'s Vec<parser::ArgumentDef<'s>>, fields: &Vec<parser::Field>) {
+        self.variables = Some(args);
+        self.variable_definitions(args);
+        match root {
+            Some(root) => self.fields(root, fields),
+            None => self.diagnostics.push(missing_root(kind, self.path, location)),
+        }
+        self.merge_conflicts(fields);
+    }
+
+    //variables are sent to the server, so their types have to be scalars, enums or input objects
+    fn variable_definitions(&mut self, args: &Vec<parser::ArgumentDef>) {
+        for arg in args {
+            let name = scalar_or_named(&arg.kind);
+            match self.schema.get(name) {
+                Some(named) if named.kind == NamedTypeKind::Object || named.kind == NamedTypeKind::Interface => {
+                    self.error("C006", format!("Variable ${} has type {} which is not an input type", arg.name, name), arg.location, "variable type")
+                },
+                None if !BUILTIN_SCALARS.contains(&name) => self.error("C002", format!("Unknown type {}", name), arg.location, "variable type"),
+                _ => if let Some(default) = &arg.default {
+                    self.value(default, Some(&arg.kind), arg.location)
+                },
+            }
+        }
+    }
+
+    fn arguments(&mut self, object_type: &schema::NamedType, field: &schema::Field, args: &Vec<parser::Argument>) {
+        for arg in args {
+            match field.args.iter().find(|schema_arg| schema_arg.name == arg.name) {
+                Some(schema_arg) => self.value(&arg.value, Some(&schema_arg.of_type), arg.location),
+                None => self.error("C007", format!("Field {}.{} has no argument {}", object_type.name, field.name, arg.name), arg.location, "unknown argument"),
+            }
+        }
+    }
+
+    //only the condition of @include and @skip has a known type
+    fn directives(&mut self, directives: &Vec<parser::Directive>) {
+        let condition = Type::NonNull(Box::new(Type::Input("Boolean".to_string())));
+        for directive in directives {
+            let conditional = directive.name == "include" || directive.name == "skip";
+            for arg in &directive.args {
+                let of_type = if conditional && arg.name == "if" { Some(&condition) } else { None };
+                self.value(&arg.value, of_type, arg.location);
+            }
+        }
+    }
+
+    fn custom_scalar(&self, of_type: &Type) -> bool {
+        if let Type::Array(_) = Codegen::nullable(of_type) { return false }
+        let name = scalar_or_named(of_type);
+        !BUILTIN_SCALARS.contains(&name) && self.schema.get(name).is_some_and(|named| named.kind == NamedTypeKind::Scalar)
+    }
+
+    //None when the type of the value is not known, then only its variables are checked
+    fn value(&mut self, value: &parser::Value, of_type: Option<&Type>, location: Location) {
+        let mismatch = |check: &mut Check, given: &str, of_type: &Type| {
+            check.error("C009", format!("{} given where {} is expected", given, type_string(of_type)), location, "argument")
+        };
+
+        match (value, of_type) {
+            (parser::Value::Variable(name), of_type) => {
+                //in fragments the variables are the ones of the operations spreading them
+                let variables = match self.variables { Some(variables) => variables, None => return };
+                match variables.iter().find(|variable| variable.name == *name) {
+                    None => self.error("C008", format!("Variable ${} is not defined", name), location, "undefined variable"),
+                    Some(variable) => if let Some(of_type) = of_type {
+                        //a nullable variable with a default is never null
+                        let with_default = variable.default.is_some() && variable_fits(&variable.kind, Codegen::nullable(of_type));
+                        if !variable_fits(&variable.kind, of_type) && !with_default {
+                            mismatch(self, &format!("Variable ${} of type {}", name, type_string(&variable.kind)), of_type);
+                        }
+                    },
+                }
+            },
+            //custom scalars can be given as any value
+            (value, Some(of_type)) if !matches!(value, parser::Value::Null) && self.custom_scalar(of_type) => self.value(value, None, location),
+            (parser::Value::List(values), Some(of_type)) => match Codegen::nullable(of_type) {
+                Type::Array(elem) => for value in values { self.value(value, Some(elem), location) },
+                _ => mismatch(self, "List", of_type),
+            },
+            (parser::Value::List(values), None) => for value in values { self.value(value, None, location) },
+            (parser::Value::Object(fields), Some(of_type)) => {
+                let named = self.schema.get(scalar_or_named(of_type)).filter(|named| named.kind == NamedTypeKind::InputObject);
+                match (named, Codegen::nullable(of_type)) {
+                    (_, Type::Array(elem)) => self.value(value, Some(elem), location),
+                    (Some(named), Type::Input(_)) => for field in fields {
+                        //schemas downloaded without the types of the input fields don't have them
+                        if named.input_fields.is_empty() { break }
+                        match named.input_fields.iter().find(|input| input.name == field.name) {
+                            Some(input) => self.value(&field.value, Some(&input.of_type), field.location),
+                            None => self.error("C007", format!("Input type {} has no field {}", named.name, field.name), field.location, "unknown field"),
+                        }
+                    },
+                    _ => mismatch(self, "Object", of_type),
+                }
+            },
+            (parser::Value::Object(fields), None) => for field in fields { self.value(&field.value, None, field.location) },
+            (parser::Value::Null, Some(of_type)) => if let Type::NonNull(_) = of_type {
+                mismatch(self, "null", of_type)
+            },
+            (value, Some(of_type)) => {
+                let given = match value {
+                    parser::Value::Int(_) => "Int",
+                    parser::Value::Float(_) => "Float",
+                    parser::Value::String(_) | parser::Value::BlockString(_) => "String",
+                    parser::Value::Bool(_) => "Boolean",
+                    _ => "Enum value",
+                };
+                let expected = match Codegen::nullable(of_type) {
+                    Type::Array(elem) => return self.value(value, Some(elem), location),
+                    of_type => scalar_or_named(of_type),
+                };
+                let fits = match value {
+                    parser::Value::Int(_) => expected == "Int" || expected == "Float" || expected == "ID",
+                    parser::Value::Float(_) => expected == "Float",
+                    parser::Value::String(_) | parser::Value::BlockString(_) => expected == "String" || expected == "ID",
+                    parser::Value::Bool(_) => expected == "Boolean",
+                    _ => self.schema.get(expected).is_some_and(|named| named.kind == NamedTypeKind::Enum),
+                };
+                if !fits { mismatch(self, given, of_type) }
+            },
+            (_, None) => (),
+        }
+    }
+
+    fn fields(&mut self, object_type: &'s schema::NamedType, fields: &Vec<parser::Field>) {
+        for field in fields {
+            match field {
+                parser::Field::PlainField(plain) => {
+                    self.directives(&plain.directives);
+                    if plain.name == "__typename" {
+                        if !plain.fields.is_empty() {
+                            self.error("C005", format!("Field {} of type {} is a scalar and cannot have a selection set", plain.name, object_type.name), plain.location, "scalar field");
+                        }
+                        continue
+                    }
+                    let schema_field = match object_type.fields.get(plain.name) {
+                        Some(schema_field) => schema_field,
+                        None => {
+                            self.error("C001", format!("Type {} has no field {}", object_type.name, plain.name), plain.location, "unknown field");
+                            continue
+                        }
+                    };
+                    self.arguments(object_type, schema_field, &plain.args);
+                    if plain.fields.is_empty() { continue }
+
+                    let named = named_type_name(&schema_field.of_type).and_then(|name| self.schema.get(name));
+                    match named {
+                        Some(named) if named.kind != NamedTypeKind::Scalar && named.kind != NamedTypeKind::Enum => self.fields(named, &plain.fields),
+                        _ => self.error("C005", format!("Field {} of type {} is a scalar and cannot have a selection set", plain.name, object_type.name), plain.location, "scalar field"),
+                    }
+                },
+                parser::Field::InlineFragment(inline) => {
+                    self.directives(&inline.directives);
+                    match &inline.on {
+                        Some(on) => if let Some(named) = self.type_condition(on, inline.location) {
+                            self.fields(named, &inline.fields)
+                        },
+                        None => self.fields(object_type, &inline.fields),
+                    }
+                },
+                parser::Field::Fragment(spread) => self.directives(&spread.directives),
+            }
+        }
     }
+}
+
+//validates the fields, type conditions, spreads, variables and arguments of the documents against the schema,
+//codegen reports the problems of the schema itself
+pub fn check(schema: &schema::Schema, documents: &DocumentSet) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for (_, module) in documents.documents() {
+        let mut check = Check{ schema, path: module.path, variables: None, diagnostics: vec![] };
 
+        for fragment in &module.fragments {
+            check.variables = None;
+            check.variable_definitions(&fragment.args);
+            check.directives(&fragment.directives);
+            if let Some(named) = check.type_condition(&fragment.on, fragment.location) {
+                check.fields(named, &fragment.fields);
+            }
+            check.merge_conflicts(&fragment.fields);
+        }
+        for query in &module.queries {
+            check.root("query", schema.query_root(), query.location, &query.args, &query.fields);
+        }
+        for mutation in &module.mutations {
+            check.root("mutation", schema.mutation_root(), mutation.location, &mutation.args, &mutation.fields);
+        }
+        for subscription in &module.subscriptions {
+            check.root("subscription", schema.subscription_root(), subscription.location, &subscription.args, &subscription.fields);
+        }
 
+        diagnostics.append(&mut check.diagnostics);
+    }
+
+    for (id, name, location) in documents.undefined_fragments() {
+        diagnostics.push(Diagnostic::error("C004", format!("Undefined fragment {}", name))
+            .with_primary(&documents.file(id).path, Some(location), "spread here"));
+    }
+
+    diagnostics
 }
 
-pub fn gen(schema: &schema::Schema, module: &parser::GraphQL) -> String {
-    let fragments_on = HashMap::new();
-    let mut codegen = Codegen{ fragments_on: &fragments_on, schema, src: "".to_string(), indent: 0 };
+//the file stem as a swift identifier, viewer-profile.graphql names its anonymous operations ViewerProfile
+fn document_name(path: &Path) -> String {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
 
-    codegen.gen_fragments(&module.fragments);
-    codegen.gen_queries(&module.queries);
-    codegen.gen_mutations(&module.mutations);
+    let mut name = String::new();
+    for part in stem.split(|c: char| !c.is_alphanumeric()).filter(|part| !part.is_empty()) {
+        let mut chars = part.chars();
+        name.extend(chars.next().unwrap().to_uppercase().chain(chars));
+    }
 
-    return codegen.src;
-}
\ No newline at end of file
+    match name.chars().next() {
+        None => "Anonymous".to_string(),
+        Some(c) if c.is_numeric() => format!("_{}", name),
+        Some(_) => name,
+    }
+}
+
+//every operation becomes a struct named after it and its kind, anonymous ones after their file,
+//so two files with the same stem or an operation named like a file give the same struct twice
+fn duplicate_operation_types(documents: &DocumentSet) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut types : HashMap<String, (&Path, Location)> = HashMap::new();
+
+    for (_, module) in documents.documents() {
+        let operations = module.queries.iter().map(|op| ("query", op.name, op.location))
+            .chain(module.mutations.iter().map(|op| ("mutation", op.name, op.location)))
+            .chain(module.subscriptions.iter().map(|op| ("subscription", op.name, op.location)));
+
+        for (kind, name, location) in operations {
+            let type_name = format!("{}{}", Codegen::swift_name(&name.map_or_else(|| document_name(module.path), str::to_string)), Codegen::swift_name(kind));
+            match types.get(&type_name) {
+                Some((first_path, first_location)) => {
+                    let mut error = Diagnostic::error("C014", format!("Operation type {} is generated more than once", type_name))
+                        .with_primary(module.path, Some(location), "generated again here")
+                        .with_secondary(*first_path, Some(*first_location), "first generated here");
+                    if name.is_none() { error = error.with_note("anonymous operations are named after their file") }
+                    diagnostics.push(error);
+                },
+                None => { types.insert(type_name, (module.path, location)); },
+            }
+        }
+    }
+
+    diagnostics
+}
+
+//generates the swift code for every file in the set, fragments can be used across files
+pub fn gen<'a>(schema: &schema::Schema, documents: &'a DocumentSet<'a>) -> Result<String, Vec<Diagnostic>> {
+    //the AST of a document with syntax errors is partial, checking it would only add noise
+    let mut diagnostics: Vec<Diagnostic> = documents.errors().iter().map(|(_, error)| error.clone()).collect();
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+        return Err(diagnostics);
+    }
+
+    diagnostics.append(&mut check(schema, documents));
+    diagnostics.append(&mut duplicate_operation_types(documents));
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+        return Err(diagnostics);
+    }
+
+    let mut codegen = Codegen{ schema, documents, path: Path::new(""), document_name: String::new(), src: "".to_string(), indent: 0 };
+
+    for (_, module) in documents.documents() {
+        codegen.path = module.path;
+        codegen.document_name = document_name(module.path);
+
+        codegen.gen_document(module).map_err(|error| vec![error])?;
+    }
+
+    Ok(codegen.src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Sources;
+    use crate::lexer::Lexer;
+    use std::path::Path;
+
+    //a schema with only a query type, with the fields a : Int, b(id: Int!, tags: [String], filter: Filter, data: JSON) : Int,
+    //user : User and node : Node
+    fn schema() -> schema::Schema {
+        let src = r#"{ "__schema": {
+            "queryType": { "name": "Query" },
+            "types": [
+                { "kind": "OBJECT", "name": "Query", "fields": [
+                    { "name": "a", "args": [], "type": { "kind": "SCALAR", "name": "Int" } },
+                    { "name": "b", "type": { "kind": "SCALAR", "name": "Int" }, "args": [
+                        { "name": "id", "type": { "kind": "NON_NULL", "ofType": { "kind": "SCALAR", "name": "Int" } } },
+                        { "name": "tags", "type": { "kind": "LIST", "ofType": { "kind": "SCALAR", "name": "String" } } },
+                        { "name": "filter", "type": { "kind": "INPUT_OBJECT", "name": "Filter" } },
+                        { "name": "data", "type": { "kind": "SCALAR", "name": "JSON" } }
+                    ] },
+                    { "name": "user", "args": [], "type": { "kind": "OBJECT", "name": "User" } },
+                    { "name": "node", "args": [], "type": { "kind": "INTERFACE", "name": "Node" } }
+                ] },
+                { "kind": "INTERFACE", "name": "Node", "fields": [
+                    { "name": "id", "args": [], "type": { "kind": "NON_NULL", "ofType": { "kind": "SCALAR", "name": "Int" } } },
+                    { "name": "name", "args": [], "type": { "kind": "SCALAR", "name": "String" } }
+                ] },
+                { "kind": "OBJECT", "name": "User", "fields": [
+                    { "name": "id", "args": [], "type": { "kind": "NON_NULL", "ofType": { "kind": "SCALAR", "name": "Int" } } },
+                    { "name": "name", "args": [], "type": { "kind": "SCALAR", "name": "String" } }
+                ] },
+                { "kind": "INPUT_OBJECT", "name": "Filter", "inputFields": [{ "name": "order", "type": { "kind": "ENUM", "name": "Order" } }] },
+                { "kind": "ENUM", "name": "Order" },
+                { "kind": "SCALAR", "name": "Int" },
+                { "kind": "SCALAR", "name": "String" },
+                { "kind": "SCALAR", "name": "Boolean" },
+                { "kind": "SCALAR", "name": "JSON" }
+            ]
+        } }"#;
+        schema::from(src).ok().unwrap()
+    }
+
+    fn check_codes(src: &str) -> Vec<&'static str> {
+        let mut sources = Sources::new();
+        sources.add("test.graphql".into(), src.to_string());
+        check(&schema(), &DocumentSet::new(&sources)).iter().map(|diagnostic| diagnostic.code).collect()
+    }
+
+    fn gen_files(files: &[(&str, &str)]) -> Result<String, Vec<Diagnostic>> {
+        let mut sources = Sources::new();
+        for (path, src) in files {
+            sources.add(path.into(), src.to_string());
+        }
+        gen(&schema(), &DocumentSet::new(&sources))
+    }
+
+    fn gen_ok(src: &str) -> String {
+        gen_files(&[("test.graphql", src)]).unwrap_or_else(|errors| panic!("{}", errors.iter().map(|error| error.to_string()).collect::<Vec<_>>().join("\n")))
+    }
+
+    //the lines follow each other in the generated code, indentation is not compared
+    fn assert_lines(src: &str, expected: &[&str]) {
+        let lines : Vec<&str> = src.lines().map(str::trim).collect();
+        assert!(lines.windows(expected.len()).any(|window| window == expected), "{:?} not in\n{}", expected, src);
+    }
+
+    fn query_fields(src: &str) -> Vec<parser::Field<'_>> {
+        let (mut document, errors) = parser::parse(Lexer::new(Path::new("test.graphql"), src));
+        assert!(errors.is_empty());
+        document.queries.remove(0).fields
+    }
+
+    fn plain<'f, 'a>(field: &'f parser::Field<'a>) -> &'f parser::PlainField<'a> {
+        match field {
+            parser::Field::PlainField(field) => field,
+            _ => panic!("not a plain field"),
+        }
+    }
+
+    #[test]
+    fn merges_fields_with_the_same_response_key() {
+        let fields = query_fields("{ a { x } b a { y } }");
+        let outer = selections(&fields);
+        assert_eq!(outer.len(), 2);
+        let merged = selections(&plain(outer[0].field()).fields);
+        assert_eq!(merged.iter().map(|s| plain(s.field()).name).collect::<Vec<_>>(), vec!["x", "y"]);
+        assert!(merged.iter().all(|s| !s.guarded));
+    }
+
+    #[test]
+    fn guarded_sub_selections_stay_optional() {
+        let fields = query_fields("query Q($c: Boolean!) { a { x } ... @include(if: $c) { a { y } } }");
+        let outer = selections(&fields);
+        assert_eq!(outer.len(), 1);
+        assert!(!outer[0].guarded);
+        let merged = selections(&plain(outer[0].field()).fields);
+        assert_eq!(merged.iter().map(|s| (plain(s.field()).name, s.guarded)).collect::<Vec<_>>(), vec![("x", false), ("y", true)]);
+    }
+
+    #[test]
+    fn field_is_optional_only_when_every_selection_is_guarded() {
+        let fields = query_fields("query Q($c: Boolean!) { a @include(if: $c) { x } a { y } b @skip(if: $c) }");
+        let outer = selections(&fields);
+        assert_eq!(outer.iter().map(|s| s.guarded).collect::<Vec<_>>(), vec![false, true]);
+        let merged = selections(&plain(outer[0].field()).fields);
+        assert_eq!(merged.iter().map(|s| (plain(s.field()).name, s.guarded)).collect::<Vec<_>>(), vec![("x", true), ("y", false)]);
+    }
+
+    #[test]
+    fn aliases_name_properties_and_coding_keys() {
+        let src = gen_ok("query Q { small: user { handle: name } node { nodeId: id label: name ... on User { name } } }");
+        assert_lines(&src, &["small: user {", "handle: name", "}"]);
+        assert_lines(&src, &["struct Small : Decodable {", "var handle : String?", "}"]);
+        assert_lines(&src, &["var nodeId : Int", "var label : String?", "var kind : Types"]);
+        assert_lines(&src, &["enum CodingKeys : String, CodingKey {", "case __typename, nodeId, label", "}"]);
+        assert_lines(&src, &[
+            "self.nodeId = try container.decode(Int.self, forKey: .nodeId)",
+            "self.label = try container.decode(String?.self, forKey: .label)",
+        ]);
+        assert_lines(&src, &["var small : Small?", "var node : Node?"]);
+    }
+
+    #[test]
+    fn conditional_fields_are_optional() {
+        let src = gen_ok("query Q($c: Boolean!) { user { id @include(if: $c) name ...UserName @skip(if: $c) } node { id @skip(if: $c) name } } fragment UserName on User { name }");
+        assert_lines(&src, &["struct User : Decodable {", "var id : Int?", "var name : String?", "var UserName : UserName?", "}"]);
+        assert_lines(&src, &["var id : Int?", "var name : String?", "var kind : Types"]);
+        assert_lines(&src, &[
+            "self.id = try container.decodeIfPresent(Int.self, forKey: .id)",
+            "self.name = try container.decode(String?.self, forKey: .name)",
+        ]);
+    }
+
+    #[test]
+    fn default_values_are_swift_literals() {
+        let src = gen_ok("query Q($id: Int! = 1, $tags: [String] = [\"a\", \"b\\\"\"], $f: Filter = {order: ASC}, $n: Int = null, $s: String) { b(id: $id, tags: $tags, filter: $f) }");
+        assert_lines(&src, &[
+            "var id : Int = 1",
+            r#"var tags : [String?]? = ["a", "b\""]"#,
+            "var f : Filter? = Filter(order: .ASC)",
+            "var n : Int? = nil",
+            "var s : String?",
+        ]);
+        assert_lines(&src, &["$id: Int! = 1", r#"$tags: [String] = ["a", "b\""]"#, "$f: Filter = {order: ASC}"]);
+    }
+
+    #[test]
+    fn document_names_are_swift_identifiers() {
+        let name = |path: &str| document_name(Path::new(path));
+        assert_eq!(name("dir/viewer-profile.graphql"), "ViewerProfile");
+        assert_eq!(name("viewerProfile.graphql"), "ViewerProfile");
+        assert_eq!(name("my profile.v2.graphql"), "MyProfileV2");
+        assert_eq!(name("2024_report.graphql"), "_2024Report");
+        assert_eq!(name("-.graphql"), "Anonymous");
+        assert_eq!(name("été.graphql"), "Été");
+    }
+
+    #[test]
+    fn anonymous_operations_are_named_after_the_file() {
+        let src = gen_files(&[("queries/viewer-profile.graphql", "{ a }"), ("2024 report.graphql", "query { user { id } }")]).ok().unwrap();
+        assert_lines(&src, &["struct ViewerProfileQuery : Encodable, GraphQLQuery {", "static let fragments : [String] = []", "static let graphql = #\"\"\"", "query {", "a", "}"]);
+        assert_lines(&src, &["struct _2024ReportQuery : Encodable, GraphQLQuery {"]);
+    }
+
+    #[test]
+    fn fragments_are_used_across_files() {
+        let src = gen_files(&[("a.graphql", "query Q { user { ...UserFields } }"), ("b.graphql", "fragment UserFields on User { id name }")]).ok().unwrap();
+        assert_lines(&src, &["struct QQuery : Encodable, GraphQLQuery {", "static let fragments : [String] = [\"UserFields\"]"]);
+        assert_lines(&src, &["struct Data : Decodable {", "var user : UserFields?", "}"]);
+        assert_lines(&src, &["struct UserFields : Decodable, Identifiable {", "var id : Int", "var name : String?", "}"]);
+        assert_lines(&src, &["meta.register(name: \"UserFields\", fragments: [], graphql: #\"\"\"", "fragment UserFields on User {"]);
+
+        let errors = gen_files(&[("a.graphql", "query Q { user { ...Missing } }"), ("b.graphql", "fragment UserFields on User { id }")]).err().unwrap();
+        assert_eq!(errors.iter().map(|error| error.code).collect::<Vec<_>>(), vec!["C004"]);
+        assert_eq!(errors[0].message, "Undefined fragment Missing");
+        let primary = errors[0].primary.as_ref().unwrap();
+        assert_eq!((primary.path.as_path(), primary.location.unwrap().column), (Path::new("a.graphql"), 21));
+    }
+
+    #[test]
+    fn duplicate_operation_types_are_an_error() {
+        let codes = |files: &[(&str, &str)]| gen_files(files).err().unwrap_or_default().iter().map(|error| error.code).collect::<Vec<_>>();
+        assert_eq!(codes(&[("a/user.graphql", "{ a }"), ("b/user.graphql", "{ a }")]), vec!["C014"]);
+        assert_eq!(codes(&[("user.graphql", "{ a }"), ("other.graphql", "query User { a }")]), vec!["C014"]);
+        assert_eq!(codes(&[("a.graphql", "query Q { a }"), ("b.graphql", "query Q { a }")]), vec!["C014"]);
+        assert!(codes(&[("a/user.graphql", "{ a }"), ("b/user-list.graphql", "{ a }"), ("c.graphql", "query Q { a }")]).is_empty());
+
+        let errors = gen_files(&[("a/user.graphql", "{ a }"), ("b/user.graphql", "\n{ a }")]).err().unwrap();
+        assert_eq!(errors[0].primary.as_ref().unwrap().path, Path::new("b/user.graphql"));
+        assert_eq!(errors[0].secondary[0].path, Path::new("a/user.graphql"));
+        assert_eq!(errors[0].notes.len(), 1);
+    }
+
+    #[test]
+    fn subscriptions_are_generated() {
+        let schema = schema::from(r#"{ "__schema": {
+            "queryType": { "name": "Query" },
+            "subscriptionType": { "name": "Subscription" },
+            "types": [
+                { "kind": "OBJECT", "name": "Query", "fields": [{ "name": "a", "args": [], "type": { "kind": "SCALAR", "name": "Int" } }] },
+                { "kind": "OBJECT", "name": "Subscription", "fields": [
+                    { "name": "tick", "args": [{ "name": "every", "type": { "kind": "SCALAR", "name": "Int" } }], "type": { "kind": "SCALAR", "name": "Int" } }
+                ] },
+                { "kind": "SCALAR", "name": "Int" }
+            ]
+        } }"#).ok().unwrap();
+        let mut sources = Sources::new();
+        sources.add("test.graphql".into(), "subscription Ticks($every: Int = 5) { tick(every: $every) }".into());
+        let src = gen(&schema, &DocumentSet::new(&sources)).ok().unwrap();
+        assert_lines(&src, &["struct TicksSubscription : Encodable, GraphQLSubscription {", "static let fragments : [String] = []"]);
+        assert_lines(&src, &["subscription Ticks($every: Int = 5) {", "tick(every: $every)", "}"]);
+        assert_lines(&src, &["var every : Int? = 5"]);
+        assert_lines(&src, &["struct Data : Decodable {", "var tick : Int?", "}"]);
+    }
+
+    #[test]
+    fn missing_subscription_root_is_an_error() {
+        let schema = schema();
+        let mut sources = Sources::new();
+        sources.add("test.graphql".into(), "subscription S { a }".into());
+        let documents = DocumentSet::new(&sources);
+
+        let mut codegen = Codegen{ schema: &schema, documents: &documents, path: Path::new(""), document_name: String::new(), src: String::new(), indent: 0 };
+        let (_, document) = documents.documents().next().unwrap();
+        let error = codegen.gen_subscriptions(document.path, &document.subscriptions).err().unwrap();
+        assert_eq!(error.code, "C003");
+        assert_eq!(error.primary.as_ref().unwrap().location.unwrap().column, 1);
+    }
+
+    #[test]
+    fn inconsistent_schema_is_an_error() {
+        let src = r#"{ "__schema": {
+            "queryType": { "name": "Query" },
+            "types": [
+                { "kind": "OBJECT", "name": "Query", "fields": [
+                    { "name": "twice", "args": [], "type": { "kind": "NON_NULL", "ofType": { "kind": "NON_NULL", "ofType": { "kind": "SCALAR", "name": "Int" } } } },
+                    { "name": "missing", "args": [], "type": { "kind": "SCALAR", "name": "Missing" } }
+                ] },
+                { "kind": "SCALAR", "name": "Int" }
+            ]
+        } }"#;
+        let schema = schema::from(src).ok().unwrap();
+
+        for (query, code) in [("query Q { twice }", "C010"), ("query Q { missing }", "C002")].iter() {
+            let mut sources = Sources::new();
+            sources.add("test.graphql".into(), query.to_string());
+            let errors = gen(&schema, &DocumentSet::new(&sources)).err().unwrap();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].code, *code);
+            assert_eq!(errors[0].primary.as_ref().unwrap().message, "query");
+        }
+    }
+
+    #[test]
+    fn checks_variable_types() {
+        assert!(check_codes("query Q($id: Int!, $tags: [String!], $f: Filter, $b: Boolean = true, $d: JSON) { a }").is_empty());
+        assert_eq!(check_codes("query Q($q: Query) { a }"), vec!["C006"]);
+        assert_eq!(check_codes("query Q($n: Nope) { a }"), vec!["C002"]);
+        assert_eq!(check_codes("query Q($n: Int = \"x\") { a }"), vec!["C009"]);
+        assert_eq!(check_codes("fragment F on Query { b(id: $anything) }"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn checks_arguments() {
+        assert!(check_codes("query Q($id: Int!, $x: Int = 1) { b(id: 1, tags: \"one\", filter: {order: ASC}, data: {any: [1]}) c: b(id: $id) d: b(id: $x) }").is_empty());
+        assert_eq!(check_codes("{ b(id: 1, nope: 2) }"), vec!["C007"]);
+        assert_eq!(check_codes("{ b(id: 1, filter: {nope: ASC}) }"), vec!["C007"]);
+        assert_eq!(check_codes("{ b(id: $id) }"), vec!["C008"]);
+        assert_eq!(check_codes("query Q($id: Int) { b(id: $id) }"), vec!["C009"]);
+        assert_eq!(check_codes("query Q($id: String!) { b(id: $id) }"), vec!["C009"]);
+        assert_eq!(check_codes("{ b(id: null) }"), vec!["C009"]);
+        assert_eq!(check_codes("{ b(id: 1.5, tags: [1], filter: {order: 1}) }"), vec!["C009", "C009", "C009"]);
+        assert_eq!(check_codes("{ b(id: [1]) }"), vec!["C009"]);
+    }
+
+    #[test]
+    fn checks_conditions() {
+        assert!(check_codes("query Q($c: Boolean!) { a @include(if: $c) ... @skip(if: true) { a } }").is_empty());
+        assert_eq!(check_codes("query Q($c: Boolean) { a @include(if: $c) }"), vec!["C009"]);
+        assert_eq!(check_codes("{ a @skip(if: $c) }"), vec!["C008"]);
+        assert_eq!(check_codes("{ a @include(if: 1) }"), vec!["C009"]);
+    }
+
+    #[test]
+    fn unwritable_default_is_an_error() {
+        let errors = gen_files(&[("test.graphql", "query Q($f: Filter = {order: ASC}, $d: JSON = {x: 1}) { a }")]).err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "C013");
+        assert_eq!(errors[0].message, "Default value of $d can't be written as a Swift literal");
+        assert_eq!(errors[0].primary.as_ref().unwrap().location.unwrap().column, 36);
+    }
+
+    #[test]
+    fn checks_fields_can_merge() {
+        assert!(check_codes("query Q($c: Boolean!) { a a user { id } user { name } c: b(id: 1, tags: [\"x\"]) c: b(tags: [\"x\"], id: 1) ... @include(if: $c) { a } }").is_empty());
+        assert_eq!(check_codes("query Q { a: user { name } a }"), vec!["C012"]);
+        assert_eq!(check_codes("{ b(id: 1) b(id: 2) }"), vec!["C012"]);
+        assert_eq!(check_codes("{ a ... { a: b(id: 1) } }"), vec!["C012"]);
+        assert_eq!(check_codes("{ user { x: id } user { x: name } }"), vec!["C012"]);
+        assert_eq!(check_codes("fragment F on Query { user { id: name id } }"), vec!["C012"]);
+
+        let mut sources = Sources::new();
+        sources.add("test.graphql".into(), "query Q { a: user { name } a }".into());
+        let diagnostics = check(&schema(), &DocumentSet::new(&sources));
+        assert_eq!(diagnostics[0].message, "Fields user and a are both selected as a");
+        assert_eq!(diagnostics[0].primary.as_ref().unwrap().location.unwrap().column, 28);
+        assert_eq!(diagnostics[0].secondary[0].location.unwrap().column, 14);
+    }
+
+    #[test]
+    fn checks_type_conditions() {
+        let messages = |src: &str| {
+            let mut sources = Sources::new();
+            sources.add("test.graphql".into(), src.to_string());
+            check(&schema(), &DocumentSet::new(&sources)).iter().map(|diagnostic| (diagnostic.code, diagnostic.message.clone())).collect::<Vec<_>>()
+        };
+        assert!(messages("fragment F on User { id } { user { ... on User { name } } }").is_empty());
+        assert_eq!(messages("fragment F on Nope { a }"), vec![("C002", "Unknown type Nope".to_string())]);
+        assert_eq!(messages("fragment F on String { a }"), vec![("C011", "Type condition on String which is not an object or interface type".to_string())]);
+        assert_eq!(messages("{ ... on Int { a } }"), vec![("C011", "Type condition on Int which is not an object or interface type".to_string())]);
+        assert_eq!(messages("{ ... on Filter { a } }"), vec![("C011", "Type condition on Filter which is not an object or interface type".to_string())]);
+    }
+
+    #[test]
+    fn syntax_errors_stop_codegen() {
+        let mut sources = Sources::new();
+        sources.add("ok.graphql".into(), "{ a }".into());
+        sources.add("broken.graphql".into(), "{ a( } fragment F on Query { a } fragment F on Query { a }".into());
+        let errors = gen(&schema(), &DocumentSet::new(&sources)).err().unwrap();
+        assert_eq!(errors.iter().map(|error| error.code).collect::<Vec<_>>(), vec!["P002", "D001"]);
+    }
+
+    #[test]
+    fn typename_is_a_string() {
+        let src = gen_ok("query Q { user { __typename id } }");
+        assert!(src.contains("struct User : Decodable, Identifiable {\n            var __typename : String\n            var id : Int\n        }"), "{}", src);
+        assert_eq!(check_codes("{ user { __typename { a } } }"), vec!["C005"]);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::{Diagnostic, Location};
+use crate::file;
+use crate::lexer::Lexer;
+use crate::parser;
+use crate::parser::{GraphQL, Fragment, FragmentSpread};
+use crate::visit::Visitor;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::io::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub usize);
+
+pub struct SourceFile {
+    pub id: FileId,
+    pub path: PathBuf,
+    pub src: String,
+}
+
+//owns the text of every file of a project, the documents of a DocumentSet borrow from it
+#[derive(Default)]
+pub struct Sources {
+    files: Vec<SourceFile>,
+}
+
+impl Sources {
+    pub fn new() -> Sources {
+        Sources{ files: vec![] }
+    }
+
+    pub fn load(files: &Vec<file::File>) -> Result<Sources> {
+        let mut sources = Sources::new();
+        for file in files {
+            let src = std::fs::read_to_string(&file.path)?;
+            sources.add(file.path.clone(), src);
+        }
+        Ok(sources)
+    }
+
+    pub fn add(&mut self, path: PathBuf, src: String) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile{ id, path, src });
+        id
+    }
+
+    pub fn files(&self) -> &Vec<SourceFile> {
+        &self.files
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+}
+
+//all .graphql files of a project, parsed together so fragments can be spread from any file
+pub struct DocumentSet<'src> {
+    sources: &'src Sources,
+    //indexed by file id
+    documents: Vec<GraphQL<'src>>,
+    errors: Vec<(FileId, Diagnostic)>,
+    //file each fragment is defined in, the first definition wins
+    fragments: HashMap<&'src str, FileId>,
+}
+
+impl<'src> DocumentSet<'src> {
+    pub fn new(sources: &'src Sources) -> DocumentSet<'src> {
+        let mut set = DocumentSet{ sources, documents: vec![], errors: vec![], fragments: HashMap::new() };
+
+        for file in sources.files() {
+            let (document, errors) = parser::parse(Lexer::new(&file.path, &file.src));
+            set.errors.extend(errors.into_iter().map(|error| (file.id, error)));
+
+            for (i, fragment) in document.fragments.iter().enumerate() {
+                let first = match set.fragments.get(fragment.name) {
+                    Some(&id) if id == file.id => document.fragments[..i].iter().find(|first| first.name == fragment.name),
+                    Some(&id) => set.document(id).fragments.iter().find(|first| first.name == fragment.name),
+                    None => {
+                        set.fragments.insert(fragment.name, file.id);
+                        continue;
+                    },
+                };
+
+                let mut error = Diagnostic::error("D001", format!("Fragment {} is defined more than once", fragment.name))
+                    .with_primary(&file.path, Some(fragment.location), "defined again here");
+                if let Some(first) = first {
+                    let id = set.fragments[fragment.name];
+                    error = error.with_secondary(&sources.file(id).path, Some(first.location), "first defined here");
+                }
+                set.errors.push((file.id, error));
+            }
+
+            set.documents.push(document);
+        }
+
+        set
+    }
+
+    pub fn files(&self) -> &'src Vec<SourceFile> {
+        self.sources.files()
+    }
+
+    pub fn file(&self, id: FileId) -> &'src SourceFile {
+        self.sources.file(id)
+    }
+
+    pub fn document(&self, id: FileId) -> &GraphQL<'src> {
+        &self.documents[id.0]
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (FileId, &GraphQL<'src>)> {
+        self.documents.iter().enumerate().map(|(i, document)| (FileId(i), document))
+    }
+
+    //syntax errors and duplicate fragments of every file, with the file they are in
+    pub fn errors(&self) -> &Vec<(FileId, Diagnostic)> {
+        &self.errors
+    }
+
+    //looks the fragment up in all files
+    pub fn fragment(&self, name: &str) -> Option<(FileId, &Fragment<'src>)> {
+        let id = *self.fragments.get(name)?;
+        let fragment = self.document(id).fragments.iter().find(|fragment| fragment.name == name)?;
+        Some((id, fragment))
+    }
+
+    //spreads of fragments that are not defined in any file
+    pub fn undefined_fragments(&self) -> Vec<(FileId, &'src str, Location)> {
+        let mut undefined = UndefinedFragments{ set: self, file: FileId(0), undefined: vec![] };
+        for (id, document) in self.documents() {
+            undefined.file = id;
+            undefined.visit_document(document);
+        }
+        undefined.undefined
+    }
+}
+
+struct UndefinedFragments<'s, 'src> {
+    set: &'s DocumentSet<'src>,
+    file: FileId,
+    undefined: Vec<(FileId, &'src str, Location)>,
+}
+
+impl<'s, 'src> Visitor<'src> for UndefinedFragments<'s, 'src> {
+    fn visit_fragment_spread(&mut self, spread: &FragmentSpread<'src>) {
+        if !self.set.fragments.contains_key(spread.name) {
+            self.undefined.push((self.file, spread.name, spread.location));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_resolve_across_files() {
+        let mut sources = Sources::new();
+        let a = sources.add("a.graphql".into(), "query A { ...F ...Missing } fragment F on User { id }".into());
+        let b = sources.add("b.graphql".into(), "query B { ...F } fragment F on User { name }".into());
+        let set = DocumentSet::new(&sources);
+
+        let (id, fragment) = set.fragment("F").unwrap();
+        assert_eq!((id, fragment.fields.len()), (a, 1));
+
+        let (id, duplicate) = &set.errors()[0];
+        assert_eq!((*id, duplicate.code), (b, "D001"));
+        assert_eq!(duplicate.primary.as_ref().unwrap().location.unwrap().column, 27);
+        assert_eq!(duplicate.secondary[0].location.unwrap().column, 38);
+
+        let undefined = set.undefined_fragments();
+        assert_eq!(undefined.iter().map(|(id, name, _)| (*id, *name)).collect::<Vec<_>>(), vec![(a, "Missing")]);
+    }
+}
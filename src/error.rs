@@ -1,4 +1,128 @@
+use crate::lexer::Span;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
 
-struct Location {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
 
-}
\ No newline at end of file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub span: Span,
+    //1-based, of the start of the span
+    pub line: u32,
+    pub column: u32,
+    //column counted in UTF-16 code units, as editors expect
+    pub utf16_column: u32,
+}
+
+#[derive(Clone)]
+pub struct Label {
+    pub path: PathBuf,
+    //None when the label points at the whole file
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+#[derive(Clone)]
+pub struct DiagnosticData {
+    pub severity: Severity,
+    //stable identifier, L for the lexer, P for the parser, S for the schema, D for the document set and C for codegen
+    pub code: &'static str,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+//every stage reports its problems as diagnostics, so tools can show them the same way
+//the data is boxed to keep the Result of every parse function small
+#[derive(Clone)]
+pub struct Diagnostic(Box<DiagnosticData>);
+
+impl Deref for Diagnostic {
+    type Target = DiagnosticData;
+
+    fn deref(&self) -> &DiagnosticData {
+        &self.0
+    }
+}
+
+impl DerefMut for Diagnostic {
+    fn deref_mut(&mut self) -> &mut DiagnosticData {
+        &mut self.0
+    }
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic(Box::new(DiagnosticData{ severity: Severity::Error, code, message: message.into(), primary: None, secondary: vec![], notes: vec![] }))
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(code, message);
+        diagnostic.severity = Severity::Warning;
+        diagnostic
+    }
+
+    pub fn with_primary(mut self, path: impl Into<PathBuf>, location: Option<Location>, message: impl Into<String>) -> Diagnostic {
+        self.primary = Some(Label{ path: path.into(), location, message: message.into() });
+        self
+    }
+
+    pub fn with_secondary(mut self, path: impl Into<PathBuf>, location: Option<Location>, message: impl Into<String>) -> Diagnostic {
+        self.secondary.push(Label{ path: path.into(), location, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    //where the primary label starts, used to order the diagnostics of a file
+    pub fn offset(&self) -> usize {
+        self.primary.as_ref().and_then(|label| label.location).map_or(0, |location| location.span.start)
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(location) = &self.location {
+            write!(f, ":{}:{}", location.line, location.column)?;
+        }
+        if !self.message.is_empty() {
+            write!(f, " {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}[{}]: {}", severity, self.code, self.message)?;
+
+        if let Some(primary) = &self.primary {
+            write!(f, "\n  --> {}", primary)?;
+        }
+        for label in &self.secondary {
+            write!(f, "\n  ... {}", label)?;
+        }
+        for note in &self.notes {
+            write!(f, "\n  = note: {}", note)?;
+        }
+        Ok(())
+    }
+}
@@ -17,7 +17,7 @@ fn visit_dir(dir: &Path, last_updated: SystemTime, result: &mut Vec<File>) -> Re
         let path = entry.path();
 
         if path.is_dir() {
-            visit_dir(&path, last_updated, result);
+            visit_dir(&path, last_updated, result)?;
         } else if let Some(extension) = path.extension() {
             let modified = metadata(&path)?.modified()? > last_updated;
             if extension == "graphql" { result.push(File{path, modified}) }
@@ -1,17 +1,98 @@
-use crate::file;
-use std::fs;
-use std::path::{PathBuf, Path};
+use std::path::Path;
 use std::fmt;
+use std::borrow::Cow;
+use crate::error::{Diagnostic, Location};
+
+//byte range into the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+const BOM : char = '\u{FEFF}';
+
+//a location in the source, lines and columns are 1-based
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: u32,
+    pub column: u32,
+    //column counted in UTF-16 code units, as editors expect
+    pub utf16_column: u32,
+}
+
+//maps byte offsets back to lines and columns, \n, \r\n and \r all end a line
+pub struct LineIndex<'a> {
+    src: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(src: &'a str) -> LineIndex<'a> {
+        let mut line_starts = vec![0];
+        let mut chars = src.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\n' => line_starts.push(i + 1),
+                '\r' if chars.peek().map(|(_, c)| *c) != Some('\n') => line_starts.push(i + 1),
+                _ => {}
+            }
+        }
+
+        LineIndex{ src, line_starts }
+    }
 
-#[derive(Debug, PartialEq)]
+    //a leading byte order mark is not counted as a column
+    fn line_start(&self, line: usize) -> usize {
+        let start = self.line_starts[line];
+        if start == 0 && self.src.starts_with(BOM) { BOM.len_utf8() } else { start }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = std::cmp::min(offset, self.src.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+
+        let mut column = 0;
+        let mut utf16_column = 0;
+        for (i, c) in self.src[self.line_start(line)..].char_indices() {
+            if self.line_start(line) + i >= offset { break }
+            column += 1;
+            utf16_column += c.len_utf16();
+        }
+
+        Position{
+            offset,
+            line: line as u32 + 1,
+            column: column + 1,
+            utf16_column: utf16_column as u32 + 1,
+        }
+    }
+
+    pub fn line_column(&self, offset: usize) -> (u32, u32) {
+        let position = self.position(offset);
+        (position.line, position.column)
+    }
+
+    //byte offset of a 1-based line and UTF-16 column, clamped to the end of the line
+    pub fn offset_of_utf16(&self, line: u32, utf16_column: u32) -> usize {
+        let line = std::cmp::min(line.saturating_sub(1) as usize, self.line_starts.len() - 1);
+        let start = self.line_start(line);
+
+        let mut utf16_column = utf16_column.saturating_sub(1) as usize;
+        for (i, c) in self.src[start..].char_indices() {
+            if utf16_column == 0 || c == '\n' || c == '\r' { return start + i }
+            utf16_column = utf16_column.saturating_sub(c.len_utf16());
+        }
+        self.src.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind<'a> {
-    FragmentKeyword,
-    QueryKeyword,
-    MutationKeyword,
-    OnKeyword,
-    StringKeyword,
-    IntKeyword,
-    BoolKeyword,
     Spread,
     OpenParen,
     CloseParen,
@@ -21,40 +102,84 @@ pub enum TokenKind<'a> {
     OpenBracket,
     CloseBracket,
     Colon,
+    Equals,
+    At,
+    Ampersand,
+    Pipe,
     Int(i32),
-    String(&'a str),
+    Float(f64),
+    String(Cow<'a, str>),
+    BlockString(Cow<'a, str>),
     Identifier(&'a str),
     Variable(&'a str),
+    //invalid text, the lexer reports an error for it and continues after it
+    Error,
+    Eof,
 }
 
+#[derive(Clone)]
 pub struct Token<'a> {
     pub kind: TokenKind<'a>,
+    pub span: Span,
     pub column: u32,
+    pub utf16_column: u32,
     pub line: u32,
+    //whitespace, commas and comments between the previous token and this one
+    pub trivia: &'a str,
 }
 
-pub struct ErrorLocation {
-    pub path: PathBuf,
-    pub column: u32,
-    pub line: u32,
+impl<'a> Token<'a> {
+    //text of each comment in the trivia, without the leading #
+    pub fn comments(&self) -> impl Iterator<Item = &'a str> {
+        self.trivia
+            .split(['\n', '\r'])
+            .filter_map(|line| {
+                let line = line.trim_start_matches([' ', '\t', ',']);
+                line.strip_prefix('#')
+            })
+    }
 }
 
 pub enum ErrorKind {
     Expecting(&'static str),
     Unexpected(char),
+    UnterminatedString,
+    InvalidEscape(char),
+    InvalidUnicodeEscape,
+    IntOverflow,
+    FloatOverflow,
+    LeadingZeros,
+    NameAfterNumber,
 }
 
-pub struct Error {
-    pub location: ErrorLocation,
-    pub kind: ErrorKind
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Expecting(str) => write!(f, "Expecting {}", str),
+            ErrorKind::Unexpected(c) => write!(f, "Unexpected token {}", c),
+            ErrorKind::UnterminatedString => write!(f, "Unterminated string"),
+            ErrorKind::InvalidEscape(c) => write!(f, "Invalid escape sequence \\{}", c),
+            ErrorKind::InvalidUnicodeEscape => write!(f, "Invalid unicode escape sequence"),
+            ErrorKind::IntOverflow => write!(f, "Int does not fit in 32 bits"),
+            ErrorKind::FloatOverflow => write!(f, "Float is out of range"),
+            ErrorKind::LeadingZeros => write!(f, "Number has leading zeros"),
+            ErrorKind::NameAfterNumber => write!(f, "Number is directly followed by a name"),
+        }
+    }
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error line {}, column {}", self.location.line, self.location.column);
-        match self.kind {
-            ErrorKind::Expecting(str) => write!(f, " : Expecting {}", str),
-            ErrorKind::Unexpected(c) => write!(f, " : Unexpected token {}", c)
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Expecting(_) => "L001",
+            ErrorKind::Unexpected(_) => "L002",
+            ErrorKind::UnterminatedString => "L003",
+            ErrorKind::InvalidEscape(_) => "L004",
+            ErrorKind::InvalidUnicodeEscape => "L005",
+            ErrorKind::IntOverflow => "L006",
+            ErrorKind::FloatOverflow => "L007",
+            ErrorKind::LeadingZeros => "L008",
+            ErrorKind::NameAfterNumber => "L009",
         }
     }
 }
@@ -64,7 +189,9 @@ struct LexerSrc<'a> {
     src: &'a str,
 }*/
 
+#[derive(Clone)]
 struct Tok<'a> {
+    trivia_slice: &'a str,
     tok_slice: &'a str,
     tok_len: usize,
 }
@@ -74,6 +201,10 @@ impl<'a> Tok<'a> {
         &self.tok_slice[..self.tok_len]
     }
 
+    fn trivia(&self) -> &'a str {
+        &self.trivia_slice[..self.trivia_slice.len() - self.tok_slice.len()]
+    }
+
     fn advance(&mut self, src_it: &mut SrcIt<'a>) {
         src_it.next();
         self.tok_len += 1;
@@ -85,11 +216,19 @@ impl<'a> Tok<'a> {
     }
 }
 
+#[derive(Clone)]
 struct SrcIt<'a> {
     path: &'a Path,
+    src: &'a str,
     i: std::str::Chars<'a>,
     column: u32,
+    utf16_column: u32,
     line: u32,
+    //position of the first character of the current token
+    start: usize,
+    start_column: u32,
+    start_utf16_column: u32,
+    start_line: u32,
 }
 
 impl<'a> SrcIt<'a> {
@@ -98,142 +237,718 @@ impl<'a> SrcIt<'a> {
     }
 
     fn next(&mut self) -> Option<char> {
-        self.column += 1;
-        self.i.next()
+        let c = self.i.next();
+        match c {
+            //\r\n is counted as a single line break
+            Some('\r') if self.current() == Some('\n') => {
+                self.column += 1;
+                self.utf16_column += 1;
+            },
+            Some('\n') | Some('\r') => {
+                self.line += 1;
+                self.column = 1;
+                self.utf16_column = 1;
+            },
+            Some(c) => {
+                self.column += 1;
+                self.utf16_column += c.len_utf16() as u32;
+            },
+            None => {}
+        }
+        c
     }
 
-    fn error(&self, kind: ErrorKind) -> Error {
-        Error{
-            location: ErrorLocation{
-                path: self.path.to_owned(),
-                column: self.column,
-                line: self.line,
-            },
-            kind
+    fn offset(&self) -> usize {
+        self.src.len() - self.i.as_str().len()
+    }
+
+    fn start_token(&mut self) {
+        self.start = self.offset();
+        self.start_column = self.column;
+        self.start_utf16_column = self.utf16_column;
+        self.start_line = self.line;
+    }
+
+    fn span(&self) -> Span {
+        Span{ start: self.start, end: self.offset() }
+    }
+
+    fn error(&self, kind: ErrorKind) -> Diagnostic {
+        let location = Location{ span: self.span(), line: self.start_line, column: self.start_column, utf16_column: self.start_utf16_column };
+        Diagnostic::error(kind.code(), kind.to_string()).with_primary(self.path, Some(location), "")
+    }
+}
+
+
+fn lex_digits<'a>(tok: &mut Tok<'a>, src_it: &mut SrcIt<'a>) -> Result<(), Diagnostic> {
+    match src_it.current() {
+        Some('0'..='9') => {},
+        _ => return Err(src_it.error(ErrorKind::Expecting("digit")))
+    }
+
+    while let Some(c) = src_it.current() {
+        match c {
+            '0'..='9' => tok.advance(src_it),
+            _ => break
         }
     }
+    Ok(())
 }
 
-fn add_token<'a>(tokens: &mut Vec<Token<'a>>, src_range: &SrcIt<'a>, kind: TokenKind<'a>) {
-    tokens.push(Token{
-        kind: kind,
-        column: src_range.column,
-        line: src_range.line
-    });
+//the sign or first digit has already been consumed
+fn lex_number<'a>(tok: &mut Tok<'a>, src_it: &mut SrcIt<'a>) -> Result<TokenKind<'a>, Diagnostic> {
+    if tok.tok() == "-" {
+        lex_digits(tok, src_it)?;
+    } else {
+        while let Some(c) = src_it.current() {
+            match c {
+                '0'..='9' => tok.advance(src_it),
+                _ => break
+            }
+        }
+    }
+
+    let digits = tok.tok().trim_start_matches('-');
+    let leading_zeros = digits.len() > 1 && digits.starts_with('0');
+
+    let mut is_float = false;
+
+    if src_it.current() == Some('.') {
+        is_float = true;
+        tok.advance(src_it);
+        lex_digits(tok, src_it)?;
+    }
+
+    if let Some('e') | Some('E') = src_it.current() {
+        is_float = true;
+        tok.advance(src_it);
+        if let Some('+') | Some('-') = src_it.current() {
+            tok.advance(src_it);
+        }
+        lex_digits(tok, src_it)?;
+    }
+
+    //123abc is one invalid token rather than a number and a name
+    if let Some('A'..='Z' | 'a'..='z' | '_') = src_it.current() {
+        while let Some('A'..='Z' | 'a'..='z' | '0'..='9' | '_') = src_it.current() {
+            tok.advance(src_it);
+        }
+        return Err(src_it.error(ErrorKind::NameAfterNumber));
+    }
 
-    //self.reset_tok();
+    if leading_zeros {
+        return Err(src_it.error(ErrorKind::LeadingZeros));
+    }
+
+    if is_float {
+        match tok.tok().parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(TokenKind::Float(value)),
+            _ => Err(src_it.error(ErrorKind::FloatOverflow))
+        }
+    } else {
+        match tok.tok().parse::<i32>() {
+            Ok(value) => Ok(TokenKind::Int(value)),
+            Err(_) => Err(src_it.error(ErrorKind::IntOverflow))
+        }
+    }
 }
 
-pub fn lex<'a>(path: &'a Path, src: &'a str) -> Result<Vec<Token<'a>>, Error> {
-    let mut tok = Tok{
-        tok_slice: src,
-        tok_len: 1
-    };
+fn lex_hex_digits(src_it: &mut SrcIt) -> Result<u32, Diagnostic> {
+    let mut value = 0;
+    for _ in 0..4 {
+        let digit = match src_it.current() {
+            Some(c) => c.to_digit(16),
+            None => None
+        };
+
+        match digit {
+            Some(digit) => value = value * 16 + digit,
+            None => return Err(src_it.error(ErrorKind::InvalidUnicodeEscape))
+        }
+        src_it.next();
+    }
+    Ok(value)
+}
+
+//the leading \u has already been consumed
+fn lex_unicode_escape(src_it: &mut SrcIt) -> Result<char, Diagnostic> {
+    let code = lex_hex_digits(src_it)?;
 
-    let mut src_it = SrcIt {
-        path: path,
-        i: src.chars(),
-        column: 0,
-        line: 0
+    //characters outside the basic multilingual plane are written as a surrogate pair
+    let code = match code {
+        0xD800..=0xDBFF => {
+            if !src_it.i.as_str().starts_with("\\u") {
+                return Err(src_it.error(ErrorKind::InvalidUnicodeEscape));
+            }
+            src_it.next();
+            src_it.next();
+            let low = lex_hex_digits(src_it)?;
+            if !(0xDC00..=0xDFFF).contains(&low) {
+                return Err(src_it.error(ErrorKind::InvalidUnicodeEscape));
+            }
+            0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+        },
+        _ => code
     };
 
-    let mut tokens = vec![];
+    match std::char::from_u32(code) {
+        Some(c) => Ok(c),
+        None => Err(src_it.error(ErrorKind::InvalidUnicodeEscape))
+    }
+}
 
-    while let Some(c) = src_it.next() {
-        match c {
-            //skip
-            ' ' | ',' | '\r' | '\t' => {},
+//the opening quote has already been consumed, only allocates if the string contains escapes
+//an invalid escape is reported once the whole string is consumed so lexing resumes after it
+fn lex_string<'a>(src_it: &mut SrcIt<'a>) -> Result<Cow<'a, str>, Diagnostic> {
+    let start = src_it.i.as_str();
+    let mut value : Option<String> = None;
+    let mut error = None;
 
-            '\n' => {
-                src_it.line += 1
-            }
+    loop {
+        let len = start.len() - src_it.i.as_str().len();
 
-            ':' => add_token(&mut tokens, &src_it, TokenKind::Colon),
-            '{' => add_token(&mut tokens, &src_it, TokenKind::OpenBracket),
-            '}' => add_token(&mut tokens, &src_it, TokenKind::CloseBracket),
-            '(' => add_token(&mut tokens, &src_it, TokenKind::OpenParen),
-            ')' => add_token(&mut tokens, &src_it, TokenKind::CloseParen),
-            '[' => add_token(&mut tokens, &src_it, TokenKind::OpenSquare),
-            ']' => add_token(&mut tokens, &src_it, TokenKind::CloseSquare),
-            '!' => add_token(&mut tokens, &src_it, TokenKind::Exclamation),
-
-
-            //spread
-            '.' => {
-                for _ in 0..2 {
-                    let found_dot = match src_it.next() {
-                        Some(c) => c == '.',
-                        None => false
-                    };
-
-                    if !found_dot {
-                        return Err(src_it.error(ErrorKind::Expecting("...")))
-                    }
-                }
+        let c = match src_it.current() {
+            None | Some('\n') | Some('\r') => return Err(src_it.error(ErrorKind::UnterminatedString)),
+            Some(c) => c
+        };
+        src_it.next();
 
-                add_token(&mut tokens, &src_it, TokenKind::Spread);
+        match c {
+            '"' => return match (error, value) {
+                (Some(error), _) => Err(error),
+                (None, Some(value)) => Ok(Cow::Owned(value)),
+                (None, None) => Ok(Cow::Borrowed(&start[..len]))
             },
+            '\\' => {
+                //a line break or the end of input is reported as unterminated on the next iteration
+                let escaped = match src_it.current() {
+                    Some('\n') | Some('\r') | None => continue,
+                    Some(escaped) => escaped
+                };
+                src_it.next();
+
+                let c = match escaped {
+                    '"' => Ok('"'),
+                    '\\' => Ok('\\'),
+                    '/' => Ok('/'),
+                    'b' => Ok('\u{8}'),
+                    'f' => Ok('\u{c}'),
+                    'n' => Ok('\n'),
+                    'r' => Ok('\r'),
+                    't' => Ok('\t'),
+                    'u' => lex_unicode_escape(src_it),
+                    c => Err(src_it.error(ErrorKind::InvalidEscape(c))),
+                };
 
-            //number
-            '0'..='9' => {
-                while let Some(c) = src_it.current() {
-                    match c {
-                        '0'..='9' => tok.advance(&mut src_it),
-                        _ => break
-                    }
-
+                match c {
+                    Ok(c) => value.get_or_insert_with(|| start[..len].to_string()).push(c),
+                    Err(e) => { error.get_or_insert(e); }
                 }
+            },
+            c => if let Some(value) = &mut value { value.push(c) }
+        }
+    }
+}
 
-                println!("Got token {}", tok.tok());
+fn is_blank(line: &str) -> bool {
+    line.chars().all(|c| c == ' ' || c == '\t')
+}
 
-                add_token(&mut tokens,&src_it, TokenKind::Int(tok.tok().parse().unwrap())); //could do the parsing ourselves
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+//removes the common indentation and the leading and trailing blank lines of a block string
+pub fn block_string_value(raw: &str) -> Cow<'_, str> {
+    if !raw.contains(['\n', '\r']) {
+        return if is_blank(raw) { Cow::Borrowed("") } else { Cow::Borrowed(raw) };
+    }
+
+    let lines : Vec<&str> = raw.split("\r\n").flat_map(|line| line.split(['\n', '\r'])).collect();
+
+    let common_indent = lines.iter()
+        .skip(1)
+        .filter(|line| !is_blank(line))
+        .map(|line| indent_of(line))
+        .min()
+        .unwrap_or(0);
+
+    let start = lines.iter().position(|line| !is_blank(line)).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !is_blank(line)).map_or(start, |i| i + 1);
+
+    let mut value = String::new();
+    for i in start..end {
+        if i > start { value.push('\n'); }
+        if i == 0 {
+            value += lines[i];
+        } else {
+            value += &lines[i][std::cmp::min(common_indent, lines[i].len())..];
+        }
+    }
+    Cow::Owned(value)
+}
+
+//the opening triple quote has already been consumed
+fn lex_block_string<'a>(src_it: &mut SrcIt<'a>) -> Result<Cow<'a, str>, Diagnostic> {
+    let start = src_it.i.as_str();
+    let mut value : Option<String> = None;
+
+    loop {
+        let rest = src_it.i.as_str();
+        let len = start.len() - rest.len();
+
+        if rest.starts_with("\"\"\"") {
+            for _ in 0..3 { src_it.next(); }
+            return Ok(match value {
+                Some(value) => Cow::Owned(block_string_value(&value).into_owned()),
+                None => block_string_value(&start[..len])
+            });
+        }
+
+        if rest.starts_with("\\\"\"\"") {
+            for _ in 0..4 { src_it.next(); }
+            value.get_or_insert_with(|| start[..len].to_string()).push_str("\"\"\"");
+            continue;
+        }
+
+        match src_it.next() {
+            None => return Err(src_it.error(ErrorKind::UnterminatedString)),
+            Some(c) => if let Some(value) = &mut value { value.push(c) }
+        }
+    }
+}
+
+//lexes a single token starting with c, returns None for whitespace and comments
+fn lex_token<'a>(c: char, tok: &mut Tok<'a>, src_it: &mut SrcIt<'a>) -> Result<Option<TokenKind<'a>>, Diagnostic> {
+    let kind = match c {
+        //skip
+        ' ' | ',' | '\r' | '\t' | '\n' | BOM => return Ok(None),
+
+        //comment
+        '#' => {
+            while let Some(c) = src_it.current() {
+                match c {
+                    '\n' | '\r' => break,
+                    _ => { src_it.next(); }
+                }
             }
+            return Ok(None)
+        }
 
-            //variable
-            '$' => {
-                while let Some(c) = src_it.current() {
-                    match c {
-                        'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => tok.advance(&mut src_it),
-                        _ => break
-                    }
+        ':' => TokenKind::Colon,
+        '=' => TokenKind::Equals,
+        '{' => TokenKind::OpenBracket,
+        '}' => TokenKind::CloseBracket,
+        '(' => TokenKind::OpenParen,
+        ')' => TokenKind::CloseParen,
+        '[' => TokenKind::OpenSquare,
+        ']' => TokenKind::CloseSquare,
+        '!' => TokenKind::Exclamation,
+        '@' => TokenKind::At,
+        '&' => TokenKind::Ampersand,
+        '|' => TokenKind::Pipe,
+
+        //spread
+        '.' => {
+            for _ in 0..2 {
+                if src_it.current() != Some('.') {
+                    return Err(src_it.error(ErrorKind::Expecting("...")))
                 }
+                src_it.next();
+            }
 
-                add_token(&mut tokens, &src_it, TokenKind::Variable(&tok.tok()[1..]));
+            TokenKind::Spread
+        },
+
+        //number
+        '-' | '0'..='9' => lex_number(tok, src_it)?,
+
+        //string
+        '"' => {
+            if src_it.i.as_str().starts_with("\"\"") {
+                src_it.next();
+                src_it.next();
+                TokenKind::BlockString(lex_block_string(src_it)?)
+            } else {
+                TokenKind::String(lex_string(src_it)?)
             }
+        }
 
-            //identifier
-            'A'..='Z' | 'a'..='z' | '_' => {
-                while let Some(c) = src_it.current() {
-                    match c {
-                        'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => tok.advance(&mut src_it),
-                        _ => break,
-                    }
+        //variable
+        '$' => {
+            while let Some(c) = src_it.current() {
+                match c {
+                    'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => tok.advance(src_it),
+                    _ => break
                 }
+            }
 
-                let str = tok.tok();
-
-                let kind = match str {
-                    "fragment" => TokenKind::FragmentKeyword,
-                    "query" => TokenKind::QueryKeyword,
-                    "mutation" => TokenKind::MutationKeyword,
-                    "on" => TokenKind::OnKeyword,
-                    "String" => TokenKind::StringKeyword,
-                    "Int" => TokenKind::IntKeyword,
-                    "Bool" => TokenKind::BoolKeyword,
-                    _ => TokenKind::Identifier(str)
-                };
+            TokenKind::Variable(&tok.tok()[1..])
+        }
+
+        //identifier
+        'A'..='Z' | 'a'..='z' | '_' => {
+            while let Some(c) = src_it.current() {
+                match c {
+                    'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => tok.advance(src_it),
+                    _ => break,
+                }
+            }
+
+            TokenKind::Identifier(tok.tok())
+        },
+
+        _ => return Err(src_it.error(ErrorKind::Unexpected(c)))
+    };
+
+    Ok(Some(kind))
+}
+
+//streams the tokens of a document, lexing resumes after an invalid token
+//each error is followed by an error token holding the invalid text
+#[derive(Clone)]
+pub struct Lexer<'a> {
+    tok: Tok<'a>,
+    src_it: SrcIt<'a>,
+    error_token: Option<Token<'a>>,
+    done: bool,
+}
 
-                add_token(&mut tokens, &src_it, kind);
+impl<'a> Lexer<'a> {
+    pub fn new(path: &'a Path, src: &'a str) -> Lexer<'a> {
+        let mut lexer = Lexer{
+            tok: Tok{
+                trivia_slice: src,
+                tok_slice: src,
+                tok_len: 1
+            },
+            src_it: SrcIt {
+                path,
+                src,
+                i: src.chars(),
+                column: 1,
+                utf16_column: 1,
+                line: 1,
+                start: 0,
+                start_column: 1,
+                start_utf16_column: 1,
+                start_line: 1,
             },
+            error_token: None,
+            done: false,
+        };
+
+        //a byte order mark is kept in the trivia of the first token but does not count as a column
+        if src.starts_with(BOM) {
+            lexer.src_it.i.next();
+            lexer.tok.reset_tok(lexer.src_it.i.as_str());
+        }
 
-            _ => {
-                return Err(src_it.error(ErrorKind::Unexpected(c)));
-            }
+        lexer
+    }
+
+    pub fn src(&self) -> &'a str {
+        self.src_it.src
+    }
+
+    pub fn path(&self) -> &'a Path {
+        self.src_it.path
+    }
+
+    fn token(&mut self, kind: TokenKind<'a>) -> Token<'a> {
+        let token = Token{
+            kind,
+            span: self.src_it.span(),
+            column: self.src_it.start_column,
+            utf16_column: self.src_it.start_utf16_column,
+            line: self.src_it.start_line,
+            trivia: self.tok.trivia(),
+        };
+
+        self.tok.trivia_slice = self.src_it.i.as_str();
+
+        token
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, Diagnostic>;
+
+    //the last token is always Eof, which holds the trailing trivia
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None }
+        if let Some(token) = self.error_token.take() { return Some(Ok(token)) }
+
+        loop {
+            self.src_it.start_token();
+
+            let c = match self.src_it.next() {
+                Some(c) => c,
+                None => {
+                    self.done = true;
+                    return Some(Ok(self.token(TokenKind::Eof)));
+                }
+            };
+
+            let result = match lex_token(c, &mut self.tok, &mut self.src_it) {
+                Ok(Some(kind)) => Some(Ok(self.token(kind))),
+                Ok(None) => None,
+                Err(error) => {
+                    self.error_token = Some(self.token(TokenKind::Error));
+                    Some(Err(error))
+                },
+            };
+
+            self.tok.reset_tok(self.src_it.i.as_str());
+
+            if result.is_some() { return result }
+        }
+    }
+}
+
+pub fn lex<'a>(path: &'a Path, src: &'a str) -> (Vec<Token<'a>>, Vec<Diagnostic>) {
+    let mut tokens = vec![];
+    let mut errors = vec![];
+
+    for result in Lexer::new(path, src) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    (tokens, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_kinds(src: &str) -> (Vec<TokenKind<'_>>, Vec<&'static str>) {
+        let (tokens, errors) = lex(Path::new("test.graphql"), src);
+        (tokens.into_iter().map(|token| token.kind).collect(), errors.iter().map(|error| error.code).collect())
+    }
+
+    fn kinds(src: &str) -> Vec<TokenKind<'_>> {
+        let (kinds, errors) = lex_kinds(src);
+        assert!(errors.is_empty());
+        kinds
+    }
+
+    fn error_code(src: &str) -> &'static str {
+        lex_kinds(src).1.remove(0)
+    }
+
+    fn string_value(src: &str) -> Cow<'_, str> {
+        match kinds(src).remove(0) {
+            TokenKind::String(value) | TokenKind::BlockString(value) => value,
+            _ => panic!("not a string")
         }
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string_value(r#""a\"b\\c\/d\b\f\n\r\t""#), "a\"b\\c/d\u{8}\u{c}\n\r\t");
+        assert_eq!(string_value(r#""café""#), "café");
+        assert_eq!(string_value(r#""é😀""#), "é😀");
+        assert_eq!(string_value(r#""naïve 日本""#), "naïve 日本");
+    }
+
+    #[test]
+    fn strings_without_escapes_are_borrowed() {
+        let (tokens, _) = lex(Path::new("test.graphql"), "\"plain\" \"esc\\n\"");
+        assert!(matches!(tokens[0].kind, TokenKind::String(Cow::Borrowed("plain"))));
+        assert!(matches!(tokens[1].kind, TokenKind::String(Cow::Owned(_))));
+    }
+
+    #[test]
+    fn invalid_strings() {
+        assert_eq!(error_code(r#""\x" a"#), "L004");
+        assert_eq!(error_code(r#""\uD83D" a"#), "L005");
+        assert_eq!(error_code(r#""\u12G4""#), "L005");
+        assert_eq!(error_code("\"open\nb"), "L003");
+        assert_eq!(error_code("\"\"\"open"), "L003");
+        //lexing continues after the string
+        assert_eq!(lex_kinds(r#""\x" a"#).0, vec![TokenKind::Error, TokenKind::Identifier("a"), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(kinds("-12 3.25 1e3 -2.5E-2 6E+1"), vec![
+            TokenKind::Int(-12),
+            TokenKind::Float(3.25),
+            TokenKind::Float(1000.0),
+            TokenKind::Float(-0.025),
+            TokenKind::Float(60.0),
+            TokenKind::Eof,
+        ]);
+        assert_eq!(kinds("0 -0 0.5 0e1"), vec![TokenKind::Int(0), TokenKind::Int(0), TokenKind::Float(0.5), TokenKind::Float(0.0), TokenKind::Eof]);
+        assert_eq!(kinds("-2147483648"), vec![TokenKind::Int(-2147483648), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn number_errors() {
+        assert_eq!(error_code("3000000000"), "L006");
+        assert_eq!(error_code("1e400"), "L007");
+        assert_eq!(error_code("1."), "L001");
+        assert_eq!(error_code("- 1"), "L001");
+        assert_eq!(error_code("1e"), "L001");
+    }
+
+    #[test]
+    fn rejects_name_after_number() {
+        assert_eq!(error_code("123abc"), "L009");
+        assert_eq!(error_code("1.5e3x"), "L009");
+    }
+
+    #[test]
+    fn comments_are_trivia() {
+        let (tokens, errors) = lex(Path::new("test.graphql"), "# first\n  #second\nquery # trailing\n{}");
+        assert!(errors.is_empty());
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("query"));
+        assert_eq!(tokens[0].comments().collect::<Vec<_>>(), vec![" first", "second"]);
+        assert_eq!(tokens[1].comments().collect::<Vec<_>>(), vec![" trailing"]);
+        assert_eq!(tokens[1].trivia, " # trailing\n");
+    }
+
+    #[test]
+    fn keywords_are_identifiers() {
+        assert_eq!(kinds("query String Int true null"), vec![
+            TokenKind::Identifier("query"),
+            TokenKind::Identifier("String"),
+            TokenKind::Identifier("Int"),
+            TokenKind::Identifier("true"),
+            TokenKind::Identifier("null"),
+            TokenKind::Eof,
+        ]);
+    }
+
+    #[test]
+    fn spans_lines_and_columns() {
+        let src = "query {\n  a,\r\n\tb\r\"é\" c }";
+        let (tokens, _) = lex(Path::new("test.graphql"), src);
+        let positions : Vec<(&str, u32, u32)> = tokens.iter().map(|token| (&src[token.span.start..token.span.end], token.line, token.column)).collect();
+        assert_eq!(positions, vec![
+            ("query", 1, 1),
+            ("{", 1, 7),
+            ("a", 2, 3),
+            ("b", 3, 2),
+            ("\"é\"", 4, 1),
+            ("c", 4, 5),
+            ("}", 4, 7),
+            ("", 4, 8),
+        ]);
+        assert_eq!(LineIndex::new(src).line_column(tokens[5].span.start), (4, 5));
+    }
+
+    #[test]
+    fn error_locations() {
+        let (_, errors) = lex(Path::new("test.graphql"), "a\n  ?");
+        let location = errors[0].primary.as_ref().unwrap().location.unwrap();
+        assert_eq!((location.line, location.column), (2, 3));
+        assert_eq!(location.span, Span{ start: 4, end: 5 });
+    }
+
+    #[test]
+    fn utf16_columns() {
+        let src = "query Q { a 😀 ? }\n\"é😀\" b";
+        let (tokens, errors) = lex(Path::new("test.graphql"), src);
+        let locations : Vec<_> = errors.iter().map(|error| error.primary.as_ref().unwrap().location.unwrap()).collect();
+        //the emoji is invalid text too
+        assert_eq!(locations.iter().map(|location| (location.line, location.column, location.utf16_column)).collect::<Vec<_>>(), vec![(1, 13, 13), (1, 15, 16)]);
+        let b = tokens.iter().find(|token| token.kind == TokenKind::Identifier("b")).unwrap();
+        assert_eq!((b.line, b.column, b.utf16_column), (2, 6, 7));
+        let position = LineIndex::new(src).position(b.span.start);
+        assert_eq!((position.column, position.utf16_column), (b.column, b.utf16_column));
+    }
 
+    #[test]
+    fn all_errors_are_reported() {
+        let (kinds, errors) = lex_kinds("a ? \"\\x\" b 1e ~");
+        assert_eq!(kinds, vec![
+            TokenKind::Identifier("a"),
+            TokenKind::Error,
+            TokenKind::Error,
+            TokenKind::Identifier("b"),
+            TokenKind::Error,
+            TokenKind::Error,
+            TokenKind::Eof,
+        ]);
+        assert_eq!(errors, vec!["L002", "L004", "L001", "L002"]);
+    }
+
+    #[test]
+    fn error_token_holds_invalid_text() {
+        let src = "a \u{201C}b\u{201D} ..c";
+        let (tokens, errors) = lex(Path::new("test.graphql"), src);
+        assert_eq!(errors.iter().map(|error| error.code).collect::<Vec<_>>(), vec!["L002", "L002", "L001"]);
+
+        let texts : Vec<(&TokenKind, &str)> = tokens.iter().map(|token| (&token.kind, &src[token.span.start..token.span.end])).collect();
+        assert_eq!(texts, vec![
+            (&TokenKind::Identifier("a"), "a"),
+            (&TokenKind::Error, "\u{201C}"),
+            (&TokenKind::Identifier("b"), "b"),
+            (&TokenKind::Error, "\u{201D}"),
+            (&TokenKind::Error, ".."),
+            (&TokenKind::Identifier("c"), "c"),
+            (&TokenKind::Eof, ""),
+        ]);
+    }
+
+    #[test]
+    fn iterator_ends_after_eof() {
+        let mut lexer = Lexer::new(Path::new("test.graphql"), "a");
+        assert_eq!(lexer.next().unwrap().ok().unwrap().kind, TokenKind::Identifier("a"));
+        assert_eq!(lexer.next().unwrap().ok().unwrap().kind, TokenKind::Eof);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn bom_is_skipped() {
+        let src = "\u{FEFF}query";
+        let (tokens, errors) = lex(Path::new("test.graphql"), src);
+        assert!(errors.is_empty());
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("query"));
+        assert_eq!((tokens[0].line, tokens[0].column), (1, 1));
+        assert_eq!(tokens[0].trivia, "\u{FEFF}");
+        assert_eq!(LineIndex::new(src).line_column(tokens[0].span.start), (1, 1));
+    }
 
-        tok.reset_tok(src_it.i.as_str());
+    #[test]
+    fn line_index_utf16_columns() {
+        let src = "a\r\n😀 é b\rc\nd";
+        let index = LineIndex::new(src);
+        let b = src.find('b').unwrap();
+        let position = index.position(b);
+        assert_eq!((position.line, position.column, position.utf16_column), (2, 5, 6));
+        assert_eq!(index.line_column(src.find('c').unwrap()), (3, 1));
+        assert_eq!(index.line_column(src.find('d').unwrap()), (4, 1));
+        assert_eq!(index.offset_of_utf16(2, 6), b);
+        //clamped to the end of the line
+        assert_eq!(index.offset_of_utf16(2, 100), b + 1);
     }
 
-    Ok(tokens)
-}
\ No newline at end of file
+    #[test]
+    fn rejects_leading_zeros() {
+        assert_eq!(error_code("007"), "L008");
+        assert_eq!(error_code("-01"), "L008");
+        assert_eq!(error_code("00.5"), "L008");
+    }
+
+    #[test]
+    fn block_strings() {
+        assert_eq!(string_value("\"\"\"\n    Hello,\n      World!\n\n    Yours\n  \"\"\""), "Hello,\n  World!\n\nYours");
+        assert_eq!(string_value("\"\"\"  one line  \"\"\""), "  one line  ");
+        assert_eq!(string_value("\"\"\"a \\\"\"\" b\"\"\""), "a \"\"\" b");
+        assert_eq!(string_value("\"\"\"no \\n escapes\"\"\""), "no \\n escapes");
+        assert_eq!(string_value("\"\"\"\r\n  a\r\n  b\r\n\"\"\""), "a\nb");
+    }
+
+    #[test]
+    fn block_string_value_strips_indentation() {
+        assert_eq!(block_string_value("first\n  second\n    third"), "first\nsecond\n  third");
+        assert_eq!(block_string_value("\n\n  a\n\n"), "a");
+        assert_eq!(block_string_value("   "), "");
+    }
+}
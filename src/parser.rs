@@ -1,13 +1,24 @@
-use crate::lexer::{TokenKind, Token};
-use crate::lexer::ErrorKind::Unexpected;
+use crate::lexer::{TokenKind, Token, Span, Lexer};
+use crate::error::{Diagnostic, Location};
+use crate::syntax::{SyntaxKind, SyntaxNode, TreeBuilder};
+use std::borrow::Cow;
+use std::path::Path;
 
+#[derive(Clone)]
 pub enum Value<'a> {
     Int(i32),
-    String(&'a str),
+    Float(f64),
+    String(Cow<'a, str>),
+    BlockString(Cow<'a, str>),
     Bool(bool),
+    Null,
+    Enum(&'a str),
+    List(Vec<Value<'a>>),
+    Object(Vec<Argument<'a>>),
     Variable(&'a str),
 }
 
+#[derive(Clone)]
 pub enum Type {
     NonNull(Box<Type>),
     Int,
@@ -18,52 +29,185 @@ pub enum Type {
     Array(Box<Type>)
 }
 
+#[derive(Clone)]
 pub struct Argument<'a> {
     pub name: &'a str,
+    //of the name, diagnostics point there
+    pub location: Location,
     pub value: Value<'a>
 }
 
+#[derive(Clone)]
+pub struct Directive<'a> {
+    pub name: &'a str,
+    pub args: Vec<Argument<'a>>,
+}
+
+//@include and @skip can leave the selection out of the response
+pub fn is_conditional(directives: &Vec<Directive>) -> bool {
+    directives.iter().any(|directive| directive.name == "include" || directive.name == "skip")
+}
+
+#[derive(Clone)]
 pub struct PlainField<'a> {
+    pub comments: Vec<&'a str>,
+    pub alias: Option<&'a str>,
     pub name: &'a str,
+    //of the name, diagnostics point there
+    pub location: Location,
     pub args: Vec<Argument<'a>>,
+    pub directives: Vec<Directive<'a>>,
     pub fields: Vec<Field<'a>>,
 }
 
+impl<'a> PlainField<'a> {
+    //name of the field in the response
+    pub fn response_key(&self) -> &'a str {
+        self.alias.unwrap_or(self.name)
+    }
+}
+
+#[derive(Clone)]
 pub enum Field<'a> {
     PlainField(PlainField<'a>),
     InlineFragment(InlineFragment<'a>),
-    Fragment(&'a str),
+    Fragment(FragmentSpread<'a>),
+}
+
+#[derive(Clone)]
+pub struct FragmentSpread<'a> {
+    pub name: &'a str,
+    //of the name
+    pub location: Location,
+    pub directives: Vec<Directive<'a>>,
 }
 
 pub struct ArgumentDef<'a> {
     pub name: &'a str,
-    pub kind: Type
+    //of the variable
+    pub location: Location,
+    pub kind: Type,
+    pub default: Option<Value<'a>>,
+    pub directives: Vec<Directive<'a>>,
 }
 
 pub struct Query<'a> {
-    pub name: &'a str,
+    pub comments: Vec<&'a str>,
+    //None for anonymous operations
+    pub name: Option<&'a str>,
+    //of the keyword, or of the selection set of a query shorthand
+    pub location: Location,
     pub args: Vec<ArgumentDef<'a>>,
+    pub directives: Vec<Directive<'a>>,
     pub fields: Vec<Field<'a>>,
 }
 
 pub struct Mutation<'a> {
-    pub name: &'a str,
+    pub comments: Vec<&'a str>,
+    //None for anonymous operations
+    pub name: Option<&'a str>,
+    //of the keyword, or of the selection set of a query shorthand
+    pub location: Location,
+    pub args: Vec<ArgumentDef<'a>>,
+    pub directives: Vec<Directive<'a>>,
+    pub fields: Vec<Field<'a>>,
+}
+
+pub struct Subscription<'a> {
+    pub comments: Vec<&'a str>,
+    //None for anonymous operations
+    pub name: Option<&'a str>,
+    //of the keyword, or of the selection set of a query shorthand
+    pub location: Location,
     pub args: Vec<ArgumentDef<'a>>,
+    pub directives: Vec<Directive<'a>>,
     pub fields: Vec<Field<'a>>,
 }
 
 pub struct Fragment<'a> {
+    pub comments: Vec<&'a str>,
     pub name: &'a str,
+    //of the name
+    pub location: Location,
     pub args: Vec<ArgumentDef<'a>>,
     pub on: Type,
+    pub directives: Vec<Directive<'a>>,
     pub fields: Vec<Field<'a>>,
 }
 
+#[derive(Clone)]
 pub struct InlineFragment<'a> {
-    pub on: Type,
+    //None when the fragment has no type condition and selects on the enclosing type
+    pub on: Option<Type>,
+    //of the ...
+    pub location: Location,
+    pub directives: Vec<Directive<'a>>,
     pub fields: Vec<Field<'a>>,
 }
 
+//type system definitions, read from the schema in SDL
+
+pub struct RootOperation<'a> {
+    pub operation: &'a str,
+    pub type_name: &'a str,
+}
+
+pub struct SchemaDefinition<'a> {
+    pub description: Option<Cow<'a, str>>,
+    //extend schema
+    pub extension: bool,
+    pub directives: Vec<Directive<'a>>,
+    pub operations: Vec<RootOperation<'a>>,
+}
+
+pub struct InputValueDefinition<'a> {
+    pub description: Option<Cow<'a, str>>,
+    pub name: &'a str,
+    pub kind: Type,
+    pub default: Option<Value<'a>>,
+    pub directives: Vec<Directive<'a>>,
+}
+
+pub struct FieldDefinition<'a> {
+    pub description: Option<Cow<'a, str>>,
+    pub name: &'a str,
+    pub args: Vec<InputValueDefinition<'a>>,
+    pub of_type: Type,
+    pub directives: Vec<Directive<'a>>,
+}
+
+pub struct EnumValueDefinition<'a> {
+    pub description: Option<Cow<'a, str>>,
+    pub name: &'a str,
+    pub directives: Vec<Directive<'a>>,
+}
+
+pub enum TypeDefinitionKind<'a> {
+    Scalar,
+    Object{ interfaces: Vec<&'a str>, fields: Vec<FieldDefinition<'a>> },
+    Interface{ interfaces: Vec<&'a str>, fields: Vec<FieldDefinition<'a>> },
+    Union{ members: Vec<&'a str> },
+    Enum{ values: Vec<EnumValueDefinition<'a>> },
+    InputObject{ fields: Vec<InputValueDefinition<'a>> },
+}
+
+pub struct TypeDefinition<'a> {
+    pub description: Option<Cow<'a, str>>,
+    //extend type, extend enum, ...
+    pub extension: bool,
+    pub name: &'a str,
+    pub directives: Vec<Directive<'a>>,
+    pub kind: TypeDefinitionKind<'a>,
+}
+
+pub struct DirectiveDefinition<'a> {
+    pub description: Option<Cow<'a, str>>,
+    pub name: &'a str,
+    pub args: Vec<InputValueDefinition<'a>>,
+    pub repeatable: bool,
+    pub locations: Vec<&'a str>,
+}
+
 /*
 enum ASTKind<'a>{
     Value(Value<'a>),
@@ -72,115 +216,316 @@ enum ASTKind<'a>{
     Field(Field<'a>),
     Query(Query<'a>),
     Mutation(Mutation<'a>),
+    Subscription(Subscription<'a>),
     Fragment(Fragment<'a>),
 }*/
 
+//a definition of the document, as an index into the list of its kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Definition {
+    Query(usize),
+    Mutation(usize),
+    Subscription(usize),
+    Fragment(usize),
+    Schema(usize),
+    Type(usize),
+    Directive(usize),
+}
+
 pub struct GraphQL<'a> {
+    //file the document was parsed from
+    pub path: &'a Path,
+    //every definition in source order
+    pub definitions: Vec<Definition>,
     pub fragments: Vec<Fragment<'a>>,
     pub queries: Vec<Query<'a>>,
     pub mutations: Vec<Mutation<'a>>,
+    pub subscriptions: Vec<Subscription<'a>>,
+    pub schemas: Vec<SchemaDefinition<'a>>,
+    pub types: Vec<TypeDefinition<'a>>,
+    pub directives: Vec<DirectiveDefinition<'a>>,
 }
 
 pub enum ErrorKind {
     SyntaxError,
-    Expecting(&'static str)
+    Expecting(&'static str),
+    MultipleAnonymousOperations,
+    NestingTooDeep,
+    UnterminatedDefinition,
 }
 
-//todo remove duplication from lexer
-pub struct Error {
-    column: u32,
-    line: u32,
-    kind: ErrorKind
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::SyntaxError => "P001",
+            ErrorKind::Expecting(_) => "P002",
+            ErrorKind::MultipleAnonymousOperations => "P003",
+            ErrorKind::NestingTooDeep => "P004",
+            ErrorKind::UnterminatedDefinition => "P005",
+        }
+    }
 }
 
-impl std::fmt::Display for Error {
+impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error line {}, column {}", self.line, self.column);
-        match self.kind {
-            ErrorKind::Expecting(str) => write!(f, " : Expecting {}", str),
-            ErrorKind::SyntaxError => write!(f, " : Syntax error")
+        match self {
+            ErrorKind::Expecting(str) => write!(f, "Expecting {}", str),
+            ErrorKind::SyntaxError => write!(f, "Syntax error"),
+            ErrorKind::MultipleAnonymousOperations => write!(f, "Only one anonymous operation is allowed per file"),
+            ErrorKind::NestingTooDeep => write!(f, "Nested more than {} levels deep", MAX_NESTING),
+            ErrorKind::UnterminatedDefinition => write!(f, "Expecting }} before the next definition"),
         }
     }
 }
 
+//selection sets, lists and types nested deeper than this are rejected instead of overflowing the stack
+const MAX_NESTING: u32 = 128;
+
 struct Parser<'a> {
     module: GraphQL<'a>,
-    tokens: Vec<Token<'a>>,
-    i: usize
+    lexer: Lexer<'a>,
+    //one token lookahead
+    current: Token<'a>,
+    consumed_token: bool,
+    //of the token returned by the last call to next, where an unexpected token is reported
+    last: Location,
+    //open brackets of the current definition, used to skip to its end after an error
+    depth: u32,
+    //recursion depth of the current definition
+    nesting: u32,
+    //a keyword at the start of a line inside the brackets of the current definition, with the lexer right after it,
+    //when the definition turns out to be broken it was most likely not closed and parsing resumes there
+    next_definition: Option<(Token<'a>, Lexer<'a>)>,
+    errors: Vec<Diagnostic>,
+    //only built when the lossless syntax tree is requested
+    tree: Option<TreeBuilder<'a>>,
 }
 
 impl<'a> Parser<'a> {
-    fn next(&mut self) -> &Token<'a> {
-        let i = self.i;
-        self.i += 1;
-        &self.tokens[i]
+    fn new(lexer: Lexer<'a>, lossless: bool) -> Parser<'a> {
+        let tree = if lossless { Some(TreeBuilder::new(lexer.src())) } else { None };
+
+        let mut parser = Parser{
+            module: GraphQL{
+                path: lexer.path(),
+                definitions: vec![],
+                fragments: vec![],
+                queries: vec![],
+                mutations: vec![],
+                subscriptions: vec![],
+                schemas: vec![],
+                types: vec![],
+                directives: vec![],
+            },
+            lexer,
+            current: Token{ kind: TokenKind::Eof, span: Span{ start: 0, end: 0 }, column: 1, utf16_column: 1, line: 1, trivia: "" },
+            consumed_token: false,
+            last: Location{ span: Span{ start: 0, end: 0 }, line: 1, column: 1, utf16_column: 1 },
+            depth: 0,
+            nesting: 0,
+            next_definition: None,
+            errors: vec![],
+            tree,
+        };
+        parser.current = parser.lex();
+        parser
     }
 
-    fn current(&self) -> &Token<'a> {
-        &self.tokens[self.i]
+    //the lexer has already recovered from lexical errors, they are only reported
+    //and their error tokens are kept in the syntax tree without being parsed
+    fn lex(&mut self) -> Token<'a> {
+        loop {
+            match self.lexer.next() {
+                Some(Ok(token)) if token.kind == TokenKind::Error => if let Some(tree) = &mut self.tree {
+                    tree.start_node(SyntaxKind::Error);
+                    tree.token(&token);
+                    tree.finish_node();
+                },
+                Some(Ok(token)) => return token,
+                Some(Err(error)) => self.errors.push(error),
+                None => return self.current.clone(), //keeps returning Eof
+            }
+        }
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        //Eof stays the lookahead, it is added to the syntax tree once the document is parsed
+        self.last = self.location();
+        if self.current.kind == TokenKind::Eof { return self.current.clone() }
+
+        if self.depth > 0 && self.next_definition.is_none() && self.current.column == 1 && self.is_toplevel_keyword() {
+            self.next_definition = Some((self.current.clone(), self.lexer.clone()));
+        }
+
+        //added before lexing the next token, error tokens in between follow it in the tree
+        if let Some(tree) = &mut self.tree { tree.token(&self.current) }
+
+        let next = self.lex();
+        self.consumed_token = true;
+        let token = std::mem::replace(&mut self.current, next);
+
+        match token.kind {
+            TokenKind::OpenBracket => self.depth += 1,
+            TokenKind::CloseBracket => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+
+        token
     }
 
-    fn error(&self, kind: ErrorKind) -> Error {
-        let i = std::cmp::min(self.i, self.tokens.len() - 1);
+    fn start_node(&mut self, kind: SyntaxKind) {
+        if let Some(tree) = &mut self.tree { tree.start_node(kind) }
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        match &mut self.tree {
+            Some(tree) => tree.checkpoint(),
+            None => 0
+        }
+    }
+
+    fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        if let Some(tree) = &mut self.tree { tree.start_node_at(checkpoint, kind) }
+    }
+
+    fn finish_node(&mut self) {
+        if let Some(tree) = &mut self.tree { tree.finish_node() }
+    }
 
-        Error{
-            kind,
-            column: self.tokens[i].column,
-            line: self.tokens[i].line,
+    fn enter(&mut self) -> Result<(), Diagnostic> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(self.error(ErrorKind::NestingTooDeep));
         }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.nesting -= 1;
     }
 
-    fn expect(&mut self, kind: TokenKind, expecting: &'static str) -> Result<(), Error> {
+    fn current(&self) -> &Token<'a> {
+        &self.current
+    }
+
+    //of the current token, taken before it is consumed
+    fn location(&self) -> Location {
+        let token = &self.current;
+        Location{ span: token.span, line: token.line, column: token.column, utf16_column: token.utf16_column }
+    }
+
+    fn error_at(&self, location: Location, kind: ErrorKind) -> Diagnostic {
+        Diagnostic::error(kind.code(), kind.to_string()).with_primary(self.lexer.path(), Some(location), "")
+    }
+
+    //at the current token, when the error is found before consuming it
+    fn error(&self, kind: ErrorKind) -> Diagnostic {
+        self.error_at(self.location(), kind)
+    }
+
+    //at the token that was just consumed and did not match
+    fn unexpected(&self, kind: ErrorKind) -> Diagnostic {
+        self.error_at(self.last, kind)
+    }
+
+    //comments on the lines above the current token, a comment on the line of the previous token is not included
+    fn doc_comments(&self) -> Vec<&'a str> {
+        let token = self.current();
+        let mut comments = token.comments();
+
+        let after_token = self.consumed_token && token.trivia.split(['\n', '\r']).next().is_some_and(|line| line.contains('#'));
+        if after_token { comments.next(); }
+
+        comments.collect()
+    }
+
+    fn expect(&mut self, kind: TokenKind, expecting: &'static str) -> Result<(), Diagnostic> {
         if self.next().kind != kind {
-            Err(self.error(ErrorKind::Expecting(expecting)))
+            Err(self.unexpected(ErrorKind::Expecting(expecting)))
         } else {
             Ok(())
         }
     }
 
-    fn parse_name(&mut self) -> Result<&'a str, Error> {
+    fn parse_name(&mut self) -> Result<&'a str, Diagnostic> {
         match self.next().kind {
             TokenKind::Identifier(str) => Ok(str),
-            _ => Err(self.error(ErrorKind::Expecting("identifier"))),
+            _ => Err(self.unexpected(ErrorKind::Expecting("identifier"))),
+        }
+    }
+
+    fn parse_operation_name(&mut self) -> Result<Option<&'a str>, Diagnostic> {
+        if let TokenKind::Identifier(_) = self.current().kind {
+            return Ok(Some(self.parse_name()?));
+        }
+
+        //the generated type is named after the file, so there can only be one
+        let module = &self.module;
+        if module.queries.iter().any(|op| op.name.is_none())
+            || module.mutations.iter().any(|op| op.name.is_none())
+            || module.subscriptions.iter().any(|op| op.name.is_none()) {
+            return Err(self.error(ErrorKind::MultipleAnonymousOperations));
         }
+
+        Ok(None)
     }
 
-    fn parse_type(&mut self) -> Result<Type, Error> {
+    fn parse_type(&mut self) -> Result<Type, Diagnostic> {
+        self.enter()?;
+        self.start_node(SyntaxKind::Type);
+
         let result = match self.next().kind {
+            TokenKind::Identifier("String") => Ok(Type::String),
+            TokenKind::Identifier("Int") => Ok(Type::Int),
+            TokenKind::Identifier("Bool") => Ok(Type::Bool),
             TokenKind::Identifier(name) => Ok(Type::Input(name.to_string())),
-            TokenKind::StringKeyword => Ok(Type::String),
-            TokenKind::IntKeyword => Ok(Type::Int),
-            TokenKind::BoolKeyword => Ok(Type::Bool),
             TokenKind::OpenSquare => {
                 let elem = self.parse_type()?;
                 self.expect(TokenKind::CloseSquare, "]")?;
                 Ok(Type::Array(Box::new(elem)))
             }
-            _ => Err(self.error(ErrorKind::Expecting("type")))
+            _ => Err(self.unexpected(ErrorKind::Expecting("type")))
         }?;
 
-        if self.current().kind == TokenKind::Exclamation {
+        let result = if self.current().kind == TokenKind::Exclamation {
             self.next();
-            Ok(Type::NonNull(Box::new(result)))
+            Type::NonNull(Box::new(result))
         } else {
-            Ok(result)
-        }
+            result
+        };
+
+        self.finish_node();
+        self.leave();
+        Ok(result)
     }
 
-    fn parse_spread(&mut self) -> Result<Field<'a>, Error> {
+    //the ... has already been consumed
+    fn parse_spread(&mut self, location: Location) -> Result<Field<'a>, Diagnostic> {
+        if let TokenKind::At | TokenKind::OpenBracket = self.current().kind {
+            let directives = self.parse_directives()?;
+            let fields = self.parse_fields()?;
+
+            return Ok(Field::InlineFragment(InlineFragment{on: None, location, directives, fields}))
+        }
+
+        let name_location = self.location();
         match self.next().kind {
-            TokenKind::Identifier(name) => Ok(Field::Fragment(name)),
-            TokenKind::OnKeyword => {
-                let on = self.parse_type()?;
+            TokenKind::Identifier("on") => {
+                let on = Some(self.parse_type()?);
+                let directives = self.parse_directives()?;
                 let fields = self.parse_fields()?;
 
-                Ok(Field::InlineFragment(InlineFragment{on, fields}))
+                Ok(Field::InlineFragment(InlineFragment{on, location, directives, fields}))
+            },
+            TokenKind::Identifier(name) => {
+                let directives = self.parse_directives()?;
+                Ok(Field::Fragment(FragmentSpread{name, location: name_location, directives}))
             },
-            _ => Err(self.error(ErrorKind::Expecting("inline fragment or fragment")))
+            _ => Err(self.unexpected(ErrorKind::Expecting("inline fragment or fragment")))
         }
     }
 
-    fn parse_optional_fields(&mut self) -> Result<Vec<Field<'a>>, Error> {
+    fn parse_optional_fields(&mut self) -> Result<Vec<Field<'a>>, Diagnostic> {
         match self.current().kind {
             TokenKind::Identifier(_) | TokenKind::CloseBracket | TokenKind::Spread => Ok(vec![]),
             TokenKind::OpenBracket => self.parse_fields(),
@@ -188,136 +533,897 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_fields(&mut self) -> Result<Vec<Field<'a>>, Error> {
+    fn parse_fields(&mut self) -> Result<Vec<Field<'a>>, Diagnostic> {
         let mut fields : Vec<Field> = vec![];
 
-        self.expect(TokenKind::OpenBracket, "{");
+        self.enter()?;
+        self.start_node(SyntaxKind::SelectionSet);
+        self.expect(TokenKind::OpenBracket, "{")?;
 
         while self.current().kind != TokenKind::CloseBracket {
             fields.push( self.parse_field()?);
         }
         self.next();
+        self.finish_node();
+        self.leave();
 
         Ok(fields)
     }
 
-    fn parse_field(&mut self) -> Result<Field<'a>, Error> {
-        match self.next().kind {
-            TokenKind::Identifier(name) => Ok(Field::PlainField(self.parse_plain_field(name)?)),
-            TokenKind::Spread => self.parse_spread(),
-            _ => Err(self.error(ErrorKind::Expecting("field or spread")))
-        }
+    fn parse_field(&mut self) -> Result<Field<'a>, Diagnostic> {
+        let comments = self.doc_comments();
+        let checkpoint = self.checkpoint();
+        let location = self.location();
+
+        let field = match self.next().kind {
+            TokenKind::Identifier(name) => {
+                self.start_node_at(checkpoint, SyntaxKind::Field);
+                Field::PlainField(self.parse_plain_field(comments, name, location)?)
+            },
+            TokenKind::Spread => {
+                let kind = match self.current().kind {
+                    TokenKind::Identifier("on") | TokenKind::At | TokenKind::OpenBracket => SyntaxKind::InlineFragment,
+                    _ => SyntaxKind::FragmentSpread
+                };
+                self.start_node_at(checkpoint, kind);
+                self.parse_spread(location)?
+            },
+            _ => return Err(self.unexpected(ErrorKind::Expecting("field or spread")))
+        };
+
+        self.finish_node();
+        Ok(field)
+    }
+
+    //constant values are used for defaults and can't refer to variables
+    fn parse_value(&mut self, constant: bool) -> Result<Value<'a>, Diagnostic> {
+        self.enter()?;
+        self.start_node(SyntaxKind::Value);
+        let value = self.parse_value_token(constant)?;
+        self.finish_node();
+        self.leave();
+        Ok(value)
     }
 
-    fn parse_value(&mut self) -> Result<Value<'a>, Error> {
+    fn parse_value_token(&mut self, constant: bool) -> Result<Value<'a>, Diagnostic> {
         match self.next().kind {
+            TokenKind::Variable(_) if constant => Err(self.unexpected(ErrorKind::Expecting("constant value"))),
             TokenKind::Variable(name) => Ok(Value::Variable(name)),
             TokenKind::Int(value) => Ok(Value::Int(value)),
+            TokenKind::Float(value) => Ok(Value::Float(value)),
             TokenKind::String(value) => Ok(Value::String(value)),
-            _ => Err(self.error(ErrorKind::Expecting("Value"))),
+            TokenKind::BlockString(value) => Ok(Value::BlockString(value)),
+            TokenKind::Identifier("true") => Ok(Value::Bool(true)),
+            TokenKind::Identifier("false") => Ok(Value::Bool(false)),
+            TokenKind::Identifier("null") => Ok(Value::Null),
+            TokenKind::Identifier(name) => Ok(Value::Enum(name)),
+            TokenKind::OpenSquare => {
+                let mut values = vec![];
+                loop {
+                    match self.current().kind {
+                        TokenKind::CloseSquare => {
+                            self.next();
+                            break
+                        },
+                        TokenKind::Eof => return Err(self.error(ErrorKind::Expecting("]"))),
+                        _ => values.push(self.parse_value(constant)?)
+                    }
+                }
+                Ok(Value::List(values))
+            },
+            TokenKind::OpenBracket => {
+                let mut fields = vec![];
+                loop {
+                    let checkpoint = self.checkpoint();
+                    let location = self.location();
+                    match self.next().kind {
+                        TokenKind::CloseBracket => break,
+                        TokenKind::Identifier(name) => {
+                            self.start_node_at(checkpoint, SyntaxKind::ObjectField);
+                            self.expect(TokenKind::Colon, ":")?;
+                            fields.push(Argument{ name, location, value: self.parse_value(constant)? });
+                            self.finish_node();
+                        },
+                        _ => return Err(self.unexpected(ErrorKind::Expecting("}")))
+                    }
+                }
+                Ok(Value::Object(fields))
+            },
+            _ => Err(self.unexpected(ErrorKind::Expecting("Value"))),
         }
     }
 
     //split into two
-    fn parse_named_list<F: Fn(&mut Parser<'a>,  &'a str) -> Result<Argument, Error>, Argument>(&mut self, variable: bool, parse: F) -> Result<Vec<Argument>, Error> {
+    fn parse_named_list<F: Fn(&mut Parser<'a>, &'a str, Location) -> Result<Argument, Diagnostic>, Argument>(&mut self, variable: bool, parse: F) -> Result<Vec<Argument>, Diagnostic> {
+        let (list_kind, item_kind) = if variable {
+            (SyntaxKind::VariableDefinitions, SyntaxKind::VariableDefinition)
+        } else {
+            (SyntaxKind::Arguments, SyntaxKind::Argument)
+        };
+
         match self.current().kind {
-            TokenKind::OpenBracket => Ok(vec![]),
-            TokenKind::Identifier(_) | TokenKind::Spread | TokenKind::CloseBracket if !variable => Ok(vec![]),
             TokenKind::OpenParen => {
+                self.start_node(list_kind);
                 self.next();
 
                 let mut args = vec![];
 
-                loop { match self.next().kind {
-                    TokenKind::Variable(name) if variable => {
-                        self.expect(TokenKind::Colon, ":")?;
-                        args.push(parse(self, name)?);
-                    },
-                    TokenKind::Identifier(name) if !variable => {
-                        self.expect(TokenKind::Colon, ":")?;
-                        args.push(parse(self, name)?);
-                    },
-                    TokenKind::CloseParen => break,
-                    _ => return Err(self.error(ErrorKind::Expecting("identifier")))
-                } }
+                loop {
+                    let checkpoint = self.checkpoint();
+                    let location = self.location();
+
+                    match self.next().kind {
+                        TokenKind::Variable(name) if variable => {
+                            self.start_node_at(checkpoint, item_kind);
+                            self.expect(TokenKind::Colon, ":")?;
+                            args.push(parse(self, name, location)?);
+                            self.finish_node();
+                        },
+                        TokenKind::Identifier(name) if !variable => {
+                            self.start_node_at(checkpoint, item_kind);
+                            self.expect(TokenKind::Colon, ":")?;
+                            args.push(parse(self, name, location)?);
+                            self.finish_node();
+                        },
+                        TokenKind::CloseParen => break,
+                        _ => return Err(self.unexpected(ErrorKind::Expecting("identifier")))
+                    }
+                }
 
+                self.finish_node();
                 Ok(args)
             },
-            _ => return Err(self.error(ErrorKind::Expecting("{ or (")))
+            _ => Ok(vec![])
+        }
+    }
+
+    fn parse_directives(&mut self) -> Result<Vec<Directive<'a>>, Diagnostic> {
+        let mut directives = vec![];
+
+        while self.current().kind == TokenKind::At {
+            self.start_node(SyntaxKind::Directive);
+            self.next();
+            let name = self.parse_name()?;
+            let args = self.parse_arguments()?;
+            self.finish_node();
+
+            directives.push(Directive{ name, args });
         }
+
+        Ok(directives)
     }
 
-    fn parse_plain_field(&mut self, name: &'a str) -> Result<PlainField<'a>, Error> {
+    fn parse_plain_field(&mut self, comments: Vec<&'a str>, name: &'a str, location: Location) -> Result<PlainField<'a>, Diagnostic> {
+        let (alias, name, location) = if self.current().kind == TokenKind::Colon {
+            self.next();
+            let location = self.location();
+            (Some(name), self.parse_name()?, location)
+        } else {
+            (None, name, location)
+        };
+
         let args = self.parse_arguments()?;
+        let directives = self.parse_directives()?;
         let fields = self.parse_optional_fields()?;
 
-        Ok(PlainField{ name, args, fields })
+        Ok(PlainField{ comments, alias, name, location, args, directives, fields })
     }
 
-    fn parse_arguments(&mut self) -> Result<Vec<Argument<'a>>, Error> {
-        self.parse_named_list(false, |parser, name|
-            Ok(Argument{ name, value: parser.parse_value()? })
+    fn parse_arguments(&mut self) -> Result<Vec<Argument<'a>>, Diagnostic> {
+        self.parse_named_list(false, |parser, name, location|
+            Ok(Argument{ name, location, value: parser.parse_value(false)? })
         )
     }
 
-    fn parse_arguments_def(&mut self) -> Result<Vec<ArgumentDef<'a>>, Error> {
-        self.parse_named_list(true, |parser, name|
-            Ok(ArgumentDef{ name, kind: parser.parse_type()? })
-        )
+    fn parse_default_value(&mut self) -> Result<Option<Value<'a>>, Diagnostic> {
+        if self.current().kind != TokenKind::Equals { return Ok(None) }
+        self.next();
+
+        Ok(Some(self.parse_value(true)?))
     }
 
-    fn parse_query(&mut self) -> Result<(), Error> {
-        let name = self.parse_name()?;
+    fn parse_arguments_def(&mut self) -> Result<Vec<ArgumentDef<'a>>, Diagnostic> {
+        self.parse_named_list(true, |parser, name, location| {
+            let kind = parser.parse_type()?;
+            let default = parser.parse_default_value()?;
+            Ok(ArgumentDef{ name, location, kind, default, directives: parser.parse_directives()? })
+        })
+    }
+
+    fn parse_query(&mut self, comments: Vec<&'a str>, location: Location) -> Result<(), Diagnostic> {
+        let name = self.parse_operation_name()?;
         let args = self.parse_arguments_def()?;
+        let directives = self.parse_directives()?;
         let fields = self.parse_fields()?;
 
-        Ok(self.module.queries.push(Query{ name, args, fields }))
+        self.module.definitions.push(Definition::Query(self.module.queries.len()));
+        self.module.queries.push(Query{ comments, name, location, args, directives, fields });
+        Ok(())
     }
 
-    fn parse_mutation(&mut self) -> Result<(), Error> {
-        let name = self.parse_name()?;
+    fn parse_mutation(&mut self, comments: Vec<&'a str>, location: Location) -> Result<(), Diagnostic> {
+        let name = self.parse_operation_name()?;
         let args = self.parse_arguments_def()?;
+        let directives = self.parse_directives()?;
         let fields = self.parse_optional_fields()?;
 
-        Ok(self.module.mutations.push(Mutation{ name, args, fields }))
+        self.module.definitions.push(Definition::Mutation(self.module.mutations.len()));
+        self.module.mutations.push(Mutation{ comments, name, location, args, directives, fields });
+        Ok(())
     }
 
-    fn parse_fragment(&mut self) -> Result<(), Error> {
-        let name = self.parse_name()?;
-        self.expect(TokenKind::OnKeyword, "Expecting on $type");
+    fn parse_subscription(&mut self, comments: Vec<&'a str>, location: Location) -> Result<(), Diagnostic> {
+        let name = self.parse_operation_name()?;
+        let args = self.parse_arguments_def()?;
+        let directives = self.parse_directives()?;
+        let fields = self.parse_fields()?;
+
+        self.module.definitions.push(Definition::Subscription(self.module.subscriptions.len()));
+        self.module.subscriptions.push(Subscription{ comments, name, location, args, directives, fields });
+        Ok(())
+    }
+
+    fn parse_fragment(&mut self, comments: Vec<&'a str>) -> Result<(), Diagnostic> {
+        let location = self.location();
+        let name = match self.parse_name()? {
+            "on" => return Err(self.unexpected(ErrorKind::Expecting("fragment name other than on"))),
+            name => name
+        };
+        self.expect(TokenKind::Identifier("on"), "on $type")?;
         let on = self.parse_type()?;
 
         let args = self.parse_arguments_def()?;
+        let directives = self.parse_directives()?;
         let fields = self.parse_fields()?;
 
-        Ok(self.module.fragments.push(Fragment{name, on, args, fields}))
+        self.module.definitions.push(Definition::Fragment(self.module.fragments.len()));
+        self.module.fragments.push(Fragment{comments, name, location, on, args, directives, fields});
+        Ok(())
+    }
+
+    fn parse_description(&mut self) -> Option<Cow<'a, str>> {
+        match self.current().kind.clone() {
+            TokenKind::String(value) | TokenKind::BlockString(value) => {
+                self.next();
+                Some(value)
+            },
+            _ => None
+        }
     }
 
-    fn parse_toplevel(&mut self) -> Result<(), Error> {
+    //a list of items between brackets, empty when there are no brackets
+    fn parse_definition_list<T, F: Fn(&mut Parser<'a>) -> Result<T, Diagnostic>>(&mut self, open: TokenKind<'a>, close: TokenKind<'a>, parse: F) -> Result<Vec<T>, Diagnostic> {
+        let mut items = vec![];
+        if self.current().kind != open { return Ok(items) }
+        self.next();
+
+        while self.current().kind != close {
+            if self.current().kind == TokenKind::Eof {
+                return Err(self.error(ErrorKind::Expecting("closing bracket")));
+            }
+            items.push(parse(self)?);
+        }
+        self.next();
+
+        Ok(items)
+    }
+
+    fn parse_input_value_definition(&mut self) -> Result<InputValueDefinition<'a>, Diagnostic> {
+        self.start_node(SyntaxKind::InputValueDefinition);
+        let description = self.parse_description();
+        let name = self.parse_name()?;
+        self.expect(TokenKind::Colon, ":")?;
+        let kind = self.parse_type()?;
+        let default = self.parse_default_value()?;
+        let directives = self.parse_directives()?;
+        self.finish_node();
+
+        Ok(InputValueDefinition{ description, name, kind, default, directives })
+    }
+
+    fn parse_arguments_definition(&mut self) -> Result<Vec<InputValueDefinition<'a>>, Diagnostic> {
+        self.parse_definition_list(TokenKind::OpenParen, TokenKind::CloseParen, Self::parse_input_value_definition)
+    }
+
+    fn parse_field_definition(&mut self) -> Result<FieldDefinition<'a>, Diagnostic> {
+        self.start_node(SyntaxKind::FieldDefinition);
+        let description = self.parse_description();
+        let name = self.parse_name()?;
+        let args = self.parse_arguments_definition()?;
+        self.expect(TokenKind::Colon, ":")?;
+        let of_type = self.parse_type()?;
+        let directives = self.parse_directives()?;
+        self.finish_node();
+
+        Ok(FieldDefinition{ description, name, args, of_type, directives })
+    }
+
+    fn parse_fields_definition(&mut self) -> Result<Vec<FieldDefinition<'a>>, Diagnostic> {
+        self.parse_definition_list(TokenKind::OpenBracket, TokenKind::CloseBracket, Self::parse_field_definition)
+    }
+
+    fn parse_enum_value_definition(&mut self) -> Result<EnumValueDefinition<'a>, Diagnostic> {
+        self.start_node(SyntaxKind::EnumValueDefinition);
+        let description = self.parse_description();
+        let name = match self.parse_name()? {
+            "true" | "false" | "null" => return Err(self.unexpected(ErrorKind::Expecting("enum value other than true, false or null"))),
+            name => name
+        };
+        let directives = self.parse_directives()?;
+        self.finish_node();
+
+        Ok(EnumValueDefinition{ description, name, directives })
+    }
+
+    fn parse_root_operation(&mut self) -> Result<RootOperation<'a>, Diagnostic> {
+        self.start_node(SyntaxKind::RootOperation);
+        let operation = match self.parse_name()? {
+            operation @ ("query" | "mutation" | "subscription") => operation,
+            _ => return Err(self.unexpected(ErrorKind::Expecting("query, mutation or subscription"))),
+        };
+        self.expect(TokenKind::Colon, ":")?;
+        let type_name = self.parse_name()?;
+        self.finish_node();
+
+        Ok(RootOperation{ operation, type_name })
+    }
+
+    //names separated by & or |, the separator is also allowed in front of the first name
+    fn parse_separated_names(&mut self, separator: TokenKind<'a>) -> Result<Vec<&'a str>, Diagnostic> {
+        if self.current().kind == separator { self.next(); }
+
+        let mut names = vec![self.parse_name()?];
+        while self.current().kind == separator {
+            self.next();
+            names.push(self.parse_name()?);
+        }
+
+        Ok(names)
+    }
+
+    fn parse_implements(&mut self) -> Result<Vec<&'a str>, Diagnostic> {
+        if self.current().kind != TokenKind::Identifier("implements") { return Ok(vec![]) }
+        self.next();
+        self.parse_separated_names(TokenKind::Ampersand)
+    }
+
+    fn parse_schema_definition(&mut self, description: Option<Cow<'a, str>>, extension: bool) -> Result<(), Diagnostic> {
+        let directives = self.parse_directives()?;
+        if !extension && self.current().kind != TokenKind::OpenBracket {
+            return Err(self.error(ErrorKind::Expecting("{")));
+        }
+        let operations = self.parse_definition_list(TokenKind::OpenBracket, TokenKind::CloseBracket, Self::parse_root_operation)?;
+
+        self.module.definitions.push(Definition::Schema(self.module.schemas.len()));
+        self.module.schemas.push(SchemaDefinition{ description, extension, directives, operations });
+        Ok(())
+    }
+
+    fn parse_type_definition(&mut self, keyword: &'a str, description: Option<Cow<'a, str>>, extension: bool) -> Result<(), Diagnostic> {
+        let name = self.parse_name()?;
+        let interfaces = if keyword == "type" || keyword == "interface" { self.parse_implements()? } else { vec![] };
+        let directives = self.parse_directives()?;
+
+        let kind = match keyword {
+            "scalar" => TypeDefinitionKind::Scalar,
+            "type" => TypeDefinitionKind::Object{ interfaces, fields: self.parse_fields_definition()? },
+            "interface" => TypeDefinitionKind::Interface{ interfaces, fields: self.parse_fields_definition()? },
+            "union" => {
+                let members = if self.current().kind == TokenKind::Equals {
+                    self.next();
+                    self.parse_separated_names(TokenKind::Pipe)?
+                } else {
+                    vec![]
+                };
+                TypeDefinitionKind::Union{ members }
+            },
+            "enum" => TypeDefinitionKind::Enum{
+                values: self.parse_definition_list(TokenKind::OpenBracket, TokenKind::CloseBracket, Self::parse_enum_value_definition)?
+            },
+            _ => TypeDefinitionKind::InputObject{
+                fields: self.parse_definition_list(TokenKind::OpenBracket, TokenKind::CloseBracket, Self::parse_input_value_definition)?
+            },
+        };
+
+        self.module.definitions.push(Definition::Type(self.module.types.len()));
+        self.module.types.push(TypeDefinition{ description, extension, name, directives, kind });
+        Ok(())
+    }
+
+    fn parse_directive_definition(&mut self, description: Option<Cow<'a, str>>) -> Result<(), Diagnostic> {
+        self.expect(TokenKind::At, "@")?;
+        let name = self.parse_name()?;
+        let args = self.parse_arguments_definition()?;
+
+        let repeatable = self.current().kind == TokenKind::Identifier("repeatable");
+        if repeatable { self.next(); }
+
+        self.expect(TokenKind::Identifier("on"), "on")?;
+        let locations = self.parse_separated_names(TokenKind::Pipe)?;
+
+        self.module.definitions.push(Definition::Directive(self.module.directives.len()));
+        self.module.directives.push(DirectiveDefinition{ description, name, args, repeatable, locations });
+        Ok(())
+    }
+
+    //schema, type and directive definitions, the keyword has already been consumed
+    fn parse_type_system_definition(&mut self, checkpoint: usize, keyword: &'a str, description: Option<Cow<'a, str>>) -> Result<(), Diagnostic> {
+        let (keyword, extension) = if keyword == "extend" {
+            match self.next().kind {
+                TokenKind::Identifier(keyword @ ("schema" | "scalar" | "type" | "interface" | "union" | "enum" | "input")) => (keyword, true),
+                _ => return Err(self.unexpected(ErrorKind::Expecting("schema or type after extend"))),
+            }
+        } else {
+            (keyword, false)
+        };
+
+        if extension && description.is_some() {
+            return Err(self.error(ErrorKind::Expecting("extension without description")));
+        }
+
+        match keyword {
+            "schema" => {
+                self.start_node_at(checkpoint, SyntaxKind::SchemaDefinition);
+                self.parse_schema_definition(description, extension)
+            },
+            "directive" => {
+                self.start_node_at(checkpoint, SyntaxKind::DirectiveDefinition);
+                self.parse_directive_definition(description)
+            },
+            _ => {
+                self.start_node_at(checkpoint, SyntaxKind::TypeDefinition);
+                self.parse_type_definition(keyword, description, extension)
+            }
+        }
+    }
+
+    fn parse_toplevel(&mut self) -> Result<(), Diagnostic> {
+        let comments = self.doc_comments();
+        let checkpoint = self.checkpoint();
+
+        //only type system definitions have descriptions
+        if let Some(description) = self.parse_description() {
+            return match self.next().kind {
+                TokenKind::Identifier(keyword @ ("schema" | "scalar" | "type" | "interface" | "union" | "enum" | "input" | "directive")) => {
+                    self.parse_type_system_definition(checkpoint, keyword, Some(description))?;
+                    self.finish_node();
+                    Ok(())
+                },
+                _ => Err(self.unexpected(ErrorKind::Expecting("type system definition after description")))
+            }
+        }
+
+        let location = self.location();
+
+        //shorthand for an anonymous query without variables or directives
+        if self.current().kind == TokenKind::OpenBracket {
+            self.start_node_at(checkpoint, SyntaxKind::Query);
+            self.parse_query(comments, location)?;
+            self.finish_node();
+            return Ok(())
+        }
+
         match self.next().kind {
-            TokenKind::MutationKeyword => self.parse_mutation(),
-            TokenKind::QueryKeyword => self.parse_query(),
-            TokenKind::FragmentKeyword => self.parse_fragment(),
-            _ => return Err(self.error(ErrorKind::Expecting("Top level consists only of query,mutation or fragment")))
+            TokenKind::Identifier("mutation") => {
+                self.start_node_at(checkpoint, SyntaxKind::Mutation);
+                self.parse_mutation(comments, location)?
+            },
+            TokenKind::Identifier("query") => {
+                self.start_node_at(checkpoint, SyntaxKind::Query);
+                self.parse_query(comments, location)?
+            },
+            TokenKind::Identifier("subscription") => {
+                self.start_node_at(checkpoint, SyntaxKind::Subscription);
+                self.parse_subscription(comments, location)?
+            },
+            TokenKind::Identifier("fragment") => {
+                self.start_node_at(checkpoint, SyntaxKind::Fragment);
+                self.parse_fragment(comments)?
+            },
+            TokenKind::Identifier(keyword @ ("schema" | "scalar" | "type" | "interface" | "union" | "enum" | "input" | "directive" | "extend")) => {
+                self.parse_type_system_definition(checkpoint, keyword, None)?
+            },
+            _ => return Err(self.unexpected(ErrorKind::Expecting("Top level consists only of query,mutation,subscription,fragment or type system definitions")))
         }
+
+        self.finish_node();
+        Ok(())
+    }
+
+    fn is_toplevel_keyword(&self) -> bool {
+        matches!(self.current().kind, TokenKind::Identifier(
+            "query" | "mutation" | "subscription" | "fragment"
+            | "schema" | "scalar" | "type" | "interface" | "union" | "enum" | "input" | "directive" | "extend"
+        ))
     }
+
+    //parses again from the keyword that started a line inside the broken definition,
+    //the errors and tokens after it are dropped and found again
+    fn resume_at(&mut self, keyword: Token<'a>, lexer: Lexer<'a>) {
+        let offset = keyword.span.start;
+        if let Some(tree) = &mut self.tree { tree.rewind(offset) }
+        self.errors.retain(|error| error.offset() < offset);
+
+        let location = Location{ span: keyword.span, line: keyword.line, column: keyword.column, utf16_column: keyword.utf16_column };
+        self.errors.push(self.error_at(location, ErrorKind::UnterminatedDefinition));
+        self.current = keyword;
+        self.lexer = lexer;
+    }
+
+    //panic mode, skips to the closing bracket of the broken definition or the next definition
+    fn recover(&mut self, error: Diagnostic) {
+        if let Some((keyword, lexer)) = self.next_definition.take() {
+            return self.resume_at(keyword, lexer);
+        }
+
+        self.errors.push(error);
+        if let Some(tree) = &mut self.tree { tree.finish_to_root() }
+        let checkpoint = self.checkpoint();
+
+        while self.current().kind != TokenKind::Eof {
+            if self.depth == 0 && self.is_toplevel_keyword() { break }
+
+            let token = self.next();
+            if token.kind == TokenKind::CloseBracket && self.depth == 0 { break }
+        }
+
+        //the skipped tokens are wrapped in an error node
+        if self.checkpoint() > checkpoint {
+            self.start_node_at(checkpoint, SyntaxKind::Error);
+            self.finish_node();
+        }
+    }
+
+    fn parse_document(&mut self) {
+        while self.current().kind != TokenKind::Eof {
+            self.depth = 0;
+            self.nesting = 0;
+            self.next_definition = None;
+            if let Err(error) = self.parse_toplevel() {
+                self.recover(error);
+            }
+        }
+
+        //Eof holds the trailing trivia
+        if let Some(tree) = &mut self.tree { tree.token(&self.current) }
+
+        //lexical errors are found ahead of the parser
+        self.errors.sort_by_key(|error| error.offset());
+    }
+}
+
+//returns every definition that could be parsed along with all errors in the document
+pub fn parse<'a>(lexer: Lexer<'a>) -> (GraphQL<'a>, Vec<Diagnostic>) {
+    let mut parser = Parser::new(lexer, false);
+    parser.parse_document();
+
+    (parser.module, parser.errors)
+}
+
+//also returns the lossless syntax tree of the document
+pub fn parse_with_syntax<'a>(lexer: Lexer<'a>) -> (GraphQL<'a>, SyntaxNode<'a>, Vec<Diagnostic>) {
+    let mut parser = Parser::new(lexer, true);
+    parser.parse_document();
+
+    let tree = parser.tree.unwrap().finish();
+    (parser.module, tree, parser.errors)
 }
 
-pub fn parse<'a>(tokens: Vec<Token<'a>>) -> Result<GraphQL<'a>, Error> {
-    let mut parser = Parser{
-        module: GraphQL{
-            fragments: vec![],
-            queries: vec![],
-            mutations: vec![],
-        },
-        tokens,
-        i: 0
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse_ok(src: &str) -> GraphQL<'_> {
+        let (document, errors) = parse(Lexer::new(Path::new("test.graphql"), src));
+        if let Some(error) = errors.first() { panic!("{}", error) }
+        document
+    }
+
+    fn plain_field<'d, 'a>(field: &'d Field<'a>) -> &'d PlainField<'a> {
+        match field {
+            Field::PlainField(field) => field,
+            _ => panic!("not a plain field")
+        }
+    }
+
+    #[test]
+    fn aliases() {
+        let document = parse_ok("query Q { smallPic: profilePic(size: 64) { url } profilePic }");
+        let fields = &document.queries[0].fields;
+        let aliased = plain_field(&fields[0]);
+        assert_eq!((aliased.alias, aliased.name, aliased.response_key()), (Some("smallPic"), "profilePic", "smallPic"));
+        assert_eq!(aliased.fields.len(), 1);
+        let plain = plain_field(&fields[1]);
+        assert_eq!((plain.alias, plain.response_key()), (None, "profilePic"));
+    }
+
+    #[test]
+    fn directives() {
+        let document = parse_ok("query Q($v: Bool @var) @op { a @include(if: $v) @other ...F @skip(if: $v) ... on User @x { b } }");
+        let query = &document.queries[0];
+        assert_eq!(query.directives[0].name, "op");
+        assert_eq!(query.args[0].directives[0].name, "var");
+
+        let field = plain_field(&query.fields[0]);
+        assert_eq!(field.directives.iter().map(|directive| directive.name).collect::<Vec<_>>(), vec!["include", "other"]);
+        assert_eq!(field.directives[0].args[0].name, "if");
+        assert!(matches!(field.directives[0].args[0].value, Value::Variable("v")));
+
+        match &query.fields[1] {
+            Field::Fragment(spread) => assert_eq!((spread.name, spread.directives[0].name), ("F", "skip")),
+            _ => panic!("not a spread")
+        }
+        match &query.fields[2] {
+            Field::InlineFragment(inline) => assert_eq!(inline.directives[0].name, "x"),
+            _ => panic!("not an inline fragment")
+        }
+    }
+
+    #[test]
+    fn doc_comments() {
+        let document = parse_ok("# the query\n# two lines\nquery Q {\n  a # not a doc comment\n  # b doc\n  b\n}");
+        let query = &document.queries[0];
+        assert_eq!(query.comments, vec![" the query", " two lines"]);
+        assert!(plain_field(&query.fields[0]).comments.is_empty());
+        assert_eq!(plain_field(&query.fields[1]).comments, vec![" b doc"]);
+    }
+
+    #[test]
+    fn keywords_as_names() {
+        let document = parse_ok("query query($String: String) { query(fragment: $String) { on mutation } } fragment String on Int { id }");
+        let field = plain_field(&document.queries[0].fields[0]);
+        assert_eq!(field.name, "query");
+        assert_eq!(field.args[0].name, "fragment");
+        assert_eq!(plain_field(&field.fields[0]).name, "on");
+        assert_eq!(document.fragments[0].name, "String");
+    }
+
+    #[test]
+    fn lexical_errors_are_all_reported() {
+        let (document, errors) = parse(Lexer::new(Path::new("test.graphql"), "query Q { a ? b ? c }"));
+        assert_eq!(errors.iter().map(|error| error.code).collect::<Vec<_>>(), vec!["L002", "L002"]);
+        let locations : Vec<_> = errors.iter().map(|error| error.primary.as_ref().unwrap().location.unwrap()).collect();
+        assert_eq!(locations.iter().map(|location| (location.line, location.column)).collect::<Vec<_>>(), vec![(1, 13), (1, 17)]);
+        assert_eq!(document.queries[0].fields.len(), 3);
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_unexpected_token() {
+        let location = |src: &str| {
+            let (_, errors) = parse(Lexer::new(Path::new("test.graphql"), src));
+            let location = errors[0].primary.as_ref().unwrap().location.unwrap();
+            (errors[0].code, location.line, location.column)
+        };
+        assert_eq!(location("query Foo bar { a }"), ("P002", 1, 11));
+        assert_eq!(location("query A { a( } query B { b }"), ("P002", 1, 14));
+        assert_eq!(location("query Q { a(x: ) }"), ("P002", 1, 16));
+        assert_eq!(location("query Q {\n  a\n  ...\n}"), ("P002", 4, 1));
+        assert_eq!(location("query Q { a"), ("P002", 1, 12));
+    }
+
+    #[test]
+    fn syntax_errors_have_utf16_columns() {
+        let (_, errors) = parse(Lexer::new(Path::new("test.graphql"), "query Q { a(s: \"😀\" 1) }"));
+        let location = errors[0].primary.as_ref().unwrap().location.unwrap();
+        assert_eq!((location.column, location.utf16_column), (20, 21));
+    }
+
+    fn names_and_errors(src: &str) -> (Vec<&str>, Vec<(&'static str, u32, u32)>) {
+        let (document, errors) = parse(Lexer::new(Path::new("test.graphql"), src));
+        let names = document.queries.iter().map(|query| query.name.unwrap_or("")).collect();
+        let errors = errors.iter().map(|error| {
+            let location = error.primary.as_ref().unwrap().location.unwrap();
+            (error.code, location.line, location.column)
+        }).collect();
+        (names, errors)
+    }
+
+    #[test]
+    fn recovers_at_the_next_definition() {
+        let (names, errors) = names_and_errors("query A { a( } query B { b } query C { c(x: ) } query D { d }");
+        assert_eq!(names, vec!["B", "D"]);
+        assert_eq!(errors, vec![("P002", 1, 14), ("P002", 1, 45)]);
+
+        let (names, errors) = names_and_errors("query A { a { b }\nquery B { b }");
+        assert_eq!(names, vec!["B"]);
+        assert_eq!(errors, vec![("P005", 2, 1)]);
+
+        let (names, errors) = names_and_errors("query A {\n  a(x: 1\nquery B { b }\nquery C {\n  c\n\nfragment F on T { f }\nquery D { d }");
+        assert_eq!(names, vec!["B", "D"]);
+        assert_eq!(errors, vec![("P005", 3, 1), ("P005", 7, 1)]);
+    }
+
+    #[test]
+    fn keywords_at_the_start_of_a_line_are_fields_in_valid_documents() {
+        let document = parse_ok("query A {\nquery\nfragment { a }\n}\nquery B { b }");
+        let names : Vec<_> = document.queries[0].fields.iter().map(|field| plain_field(field).name).collect();
+        assert_eq!(names, vec!["query", "fragment"]);
+        assert_eq!(document.queries.len(), 2);
+    }
 
+    #[test]
+    fn string_values() {
+        let document = parse_ok("query Q { a(s: \"x\\u0041\", b: \"\"\"\n    block\n  \"\"\", n: -1.5e1) }");
+        let args = &plain_field(&document.queries[0].fields[0]).args;
+        assert!(matches!(&args[0].value, Value::String(s) if s == "xA"));
+        assert!(matches!(&args[1].value, Value::BlockString(s) if s == "block"));
+        assert!(matches!(args[2].value, Value::Float(f) if f == -15.0));
+    }
 
-    while parser.i < parser.tokens.len() {
-        parser.parse_toplevel()?;
+    #[test]
+    fn values() {
+        let document = parse_ok("query Q($v: [Int] = [1, 2]) { a(b: true, n: null, e: DESC, l: [1, $v], o: { x: 1, y: { z: false } }) }");
+        assert!(matches!(&document.queries[0].args[0].default, Some(Value::List(values)) if values.len() == 2));
+        let args = &plain_field(&document.queries[0].fields[0]).args;
+        assert!(matches!(args[0].value, Value::Bool(true)));
+        assert!(matches!(args[1].value, Value::Null));
+        assert!(matches!(args[2].value, Value::Enum("DESC")));
+        assert!(matches!(&args[3].value, Value::List(values) if matches!(values[..], [Value::Int(1), Value::Variable("v")])));
+        match &args[4].value {
+            Value::Object(fields) => {
+                assert_eq!(fields.iter().map(|field| field.name).collect::<Vec<_>>(), vec!["x", "y"]);
+                assert!(matches!(&fields[1].value, Value::Object(fields) if matches!(fields[0].value, Value::Bool(false))));
+            },
+            _ => panic!("not an object")
+        }
     }
 
-    Ok(parser.module)
-}
\ No newline at end of file
+    #[test]
+    fn anonymous_operations() {
+        let document = parse_ok("{ a } fragment F on T { b }");
+        assert_eq!(document.queries[0].name, None);
+        assert_eq!(plain_field(&document.queries[0].fields[0]).name, "a");
+        let document = parse_ok("mutation($x: Int) { c(x: $x) }");
+        assert_eq!((document.mutations[0].name, document.mutations[0].args.len()), (None, 1));
+        //they are named after the file, so a second one would clash
+        let (_, errors) = parse(Lexer::new(Path::new("test.graphql"), "{ a } query { b }"));
+        assert_eq!(errors[0].code, "P003");
+    }
+
+    #[test]
+    fn type_definitions() {
+        let document = parse_ok("\"\"\"\n  A user\n\"\"\"\ntype User implements Node & Named @key(fields: \"id\") {\n  \"the id\" id: ID!\n  friends(first: Int = 10 @deprecated, after: String): [User!] @cost\n}\nextend union Result = | User | Error\nenum Role { \"admin\" ADMIN @internal USER }\ninput Filter { name: String = \"a\" }\nscalar Date");
+        assert_eq!(document.types.iter().map(|definition| definition.name).collect::<Vec<_>>(), vec!["User", "Result", "Role", "Filter", "Date"]);
+
+        let user = &document.types[0];
+        assert_eq!((user.description.as_deref(), user.extension), (Some("A user"), false));
+        assert_eq!((user.directives[0].name, user.directives[0].args[0].name), ("key", "fields"));
+        match &user.kind {
+            TypeDefinitionKind::Object{ interfaces, fields } => {
+                assert_eq!(interfaces, &vec!["Node", "Named"]);
+                assert_eq!((fields[0].name, fields[0].description.as_deref()), ("id", Some("the id")));
+                assert!(matches!(&fields[0].of_type, Type::NonNull(of_type) if matches!(of_type.as_ref(), Type::Input(name) if name == "ID")));
+                let friends = &fields[1];
+                assert_eq!(friends.args.iter().map(|arg| arg.name).collect::<Vec<_>>(), vec!["first", "after"]);
+                assert!(matches!(friends.args[0].kind, Type::Int));
+                assert!(matches!(friends.args[0].default, Some(Value::Int(10))));
+                assert_eq!(friends.args[0].directives[0].name, "deprecated");
+                assert!(matches!(&friends.of_type, Type::Array(_)));
+                assert_eq!(friends.directives[0].name, "cost");
+            },
+            _ => panic!("not an object type")
+        }
+
+        let result = &document.types[1];
+        assert!(result.extension);
+        assert!(matches!(&result.kind, TypeDefinitionKind::Union{ members } if members == &vec!["User", "Error"]));
+        match &document.types[2].kind {
+            TypeDefinitionKind::Enum{ values } => {
+                assert_eq!(values.iter().map(|value| value.name).collect::<Vec<_>>(), vec!["ADMIN", "USER"]);
+                assert_eq!((values[0].description.as_deref(), values[0].directives[0].name), (Some("admin"), "internal"));
+            },
+            _ => panic!("not an enum")
+        }
+        assert!(matches!(&document.types[3].kind, TypeDefinitionKind::InputObject{ fields } if matches!(&fields[0].default, Some(Value::String(s)) if s == "a")));
+        assert!(matches!(document.types[4].kind, TypeDefinitionKind::Scalar));
+    }
+
+    #[test]
+    fn schema_and_directive_definitions() {
+        let document = parse_ok("schema @a { query: Query mutation: Mutation }\nextend schema { subscription: Subscription }\n\"cached\" directive @cache(ttl: Int!) repeatable on FIELD | QUERY");
+        let schema = &document.schemas[0];
+        assert_eq!((schema.extension, schema.directives[0].name), (false, "a"));
+        assert_eq!(schema.operations.iter().map(|root| (root.operation, root.type_name)).collect::<Vec<_>>(), vec![("query", "Query"), ("mutation", "Mutation")]);
+        assert!(document.schemas[1].extension);
+
+        let directive = &document.directives[0];
+        assert_eq!((directive.description.as_deref(), directive.name, directive.repeatable), (Some("cached"), "cache", true));
+        assert_eq!(directive.args[0].name, "ttl");
+        assert_eq!(directive.locations, vec!["FIELD", "QUERY"]);
+    }
+
+    //xorshift, so the random inputs are the same on every run
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, below: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % below as u64) as usize
+        }
+    }
+
+    fn parse_all(src: &str) -> Vec<Diagnostic> {
+        let (_, lex_errors) = crate::lexer::lex(Path::new("fuzz.graphql"), src);
+        let (_, errors) = parse(Lexer::new(Path::new("fuzz.graphql"), src));
+        let (_, tree, _) = parse_with_syntax(Lexer::new(Path::new("fuzz.graphql"), src));
+        assert_eq!(tree.to_string(), src);
+        //the parser reports the lexical errors along with its own
+        assert!(errors.len() >= lex_errors.len());
+        errors
+    }
+
+    const VALID: &str = "# doc\nquery Q($a: [Int!]! = [1], $b: In = {x: \"s\", y: [true, null, ENUM]}) @d(if: $a) {\n  a: b(c: 1.5e3) { ...F ... on T { d } ... @skip(if: false) { e } }\n}\nfragment F on T { f(s: \"\"\"block\"\"\") }\nmutation { g }\nsubscription S { h }\n\"desc\" type T implements A & B @k { f(a: Int = 1): [T!] }\nunion U = A | B\nenum E { X Y }\ninput In { x: String }\nscalar S\nschema { query: Q }\nextend type T { g: Int }\ndirective @d(if: Boolean) repeatable on FIELD | QUERY\n";
+
+    #[test]
+    fn fuzz_truncated_input() {
+        assert!(parse_all(VALID).is_empty());
+
+        for (end, _) in VALID.char_indices().skip(1) {
+            //cuts inside a comment or between definitions are still valid documents
+            parse_all(&VALID[..end]);
+        }
+
+        for src in &["query Foo {", "query Foo { a(", "{ a(x: [1, ", "{ a(x: {b: ", "fragment F on", "query Q($a: [Int", "type T { f(a:", "\"unterminated", "\"\"\"block", "{ ...", "{ a @"] {
+            assert!(!parse_all(src).is_empty(), "no error for {}", src);
+        }
+    }
+
+    #[test]
+    fn fuzz_random_tokens() {
+        let pieces = ["query", "mutation", "subscription", "fragment", "on", "Foo", "type", "extend", "schema", "input", "enum", "union", "directive", "implements", "repeatable",
+            "{", "}", "(", ")", "[", "]", ":", "!", "=", "@", "...", "..", "&", "|", "$a", "$", "1", "-", "007", "1e", "2.5", "3000000000",
+            "\"s\"", "\"\\x\"", "\"\"\"b\"\"\"", "\"", "\"\"\"", "?", "\u{201C}", "\u{FEFF}", "#c\n", " ", ",", "\n", "\r"];
+        let mut random = Random(0x2545F4914F6CDD1D);
+
+        for _ in 0..5000 {
+            let mut src = String::new();
+            for _ in 0..random.next(40) {
+                src += pieces[random.next(pieces.len())];
+                if random.next(2) == 0 { src += " " }
+            }
+            parse_all(&src);
+        }
+    }
+
+    #[test]
+    fn fuzz_random_characters() {
+        let punctuation : Vec<char> = "{}()[]:!=@.$\"#,\\ \n".chars().collect();
+        let mut random = Random(0x9E3779B97F4A7C15);
+
+        for _ in 0..5000 {
+            let src : String = (0..random.next(60))
+                .filter_map(|_| std::char::from_u32(match random.next(4) {
+                    0 => random.next(0x80) as u32,
+                    1 => punctuation[random.next(punctuation.len())] as u32,
+                    2 => random.next(0x11000) as u32,
+                    _ => 'a' as u32 + random.next(26) as u32,
+                }))
+                .collect();
+            parse_all(&src);
+        }
+    }
+
+    #[test]
+    fn deeply_nested_input() {
+        let depth = MAX_NESTING as usize * 4;
+        let nested = |open: &str, close: &str, inner: &str| format!("{}{}{}", open.repeat(depth), inner, close.repeat(depth));
+
+        for src in &[
+            nested("{ a ", "}", ""),
+            nested("{ ... on T ", "}", "{ a }"),
+            format!("query Q($a: {}) {{ a }}", nested("[", "]", "Int")),
+            format!("{{ a(x: {}) }}", nested("[", "]", "1")),
+            format!("{{ a(x: {}) }}", nested("{b: ", "}", "1")),
+            nested("{ a ", "", ""),
+        ] {
+            let errors = parse_all(src);
+            assert!(errors.iter().any(|error| error.code == "P004"), "no nesting error for {}", &src[..20]);
+        }
+
+        let within = MAX_NESTING as usize - 2;
+        assert!(parse_all(&format!("{}{}", "{ a ".repeat(within), "}".repeat(within))).is_empty());
+    }
+}
@@ -0,0 +1,694 @@
+use crate::error::Diagnostic;
+use crate::lexer;
+use crate::lexer::{Lexer, Token, TokenKind};
+use crate::parser;
+use crate::parser::{
+    GraphQL, Definition, Query, Mutation, Subscription, Fragment, ArgumentDef, Field, PlainField, InlineFragment,
+    FragmentSpread, Directive, Argument, Value, Type, SchemaDefinition, TypeDefinition, TypeDefinitionKind,
+    FieldDefinition, InputValueDefinition, EnumValueDefinition, DirectiveDefinition,
+};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[derive(Clone, Copy)]
+pub struct PrintOptions {
+    //spaces per nesting level
+    pub indent: usize,
+    //argument lists that would make a line longer than this are put on their own lines
+    pub line_width: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> PrintOptions {
+        PrintOptions{ indent: 2, line_width: 80 }
+    }
+}
+
+//prints the AST back into canonical GraphQL, the building blocks are public so codegen can add to selection sets
+pub struct Printer {
+    options: PrintOptions,
+    out: String,
+    level: usize,
+    //off when the comments are put back from the source instead of the AST
+    comments: bool,
+}
+
+impl Printer {
+    pub fn new(options: PrintOptions) -> Printer {
+        Printer{ options, out: String::new(), level: 0, comments: true }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+
+    pub fn write(&mut self, s: &str) {
+        self.out += s;
+    }
+
+    pub fn newline(&mut self) {
+        self.out += "\n";
+        for _ in 0..self.level * self.options.indent {
+            self.out += " ";
+        }
+    }
+
+    pub fn open_block(&mut self) {
+        self.out += " {";
+        self.level += 1;
+    }
+
+    pub fn close_block(&mut self) {
+        self.level -= 1;
+        self.newline();
+        self.out += "}";
+    }
+
+    fn line_width(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    //prints the items on one line if it fits, otherwise one item per line
+    fn print_list<T, F: Fn(&mut Printer, &T)>(&mut self, open: &str, close: &str, items: &Vec<T>, print: F) {
+        if items.is_empty() { return }
+
+        let start = self.out.len();
+        self.out += open;
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 { self.out += ", "; }
+            print(self, item);
+        }
+        self.out += close;
+
+        if self.line_width() <= self.options.line_width && !self.out[start..].contains('\n') { return }
+
+        self.out.truncate(start);
+        self.out += open;
+        self.level += 1;
+        for item in items {
+            self.newline();
+            print(self, item);
+        }
+        self.level -= 1;
+        self.newline();
+        self.out += close;
+    }
+
+    pub fn print_comments(&mut self, comments: &Vec<&str>) {
+        if !self.comments { return }
+
+        for comment in comments {
+            self.out += "#";
+            self.out += comment;
+            self.newline();
+        }
+    }
+
+    pub fn print_document(&mut self, document: &GraphQL) {
+        let mut first = true;
+        let mut separate = |printer: &mut Printer| {
+            if !first {
+                printer.newline();
+                printer.newline();
+            }
+            first = false;
+        };
+
+        for definition in &document.definitions {
+            separate(self);
+            match *definition {
+                Definition::Query(i) => self.print_query(&document.queries[i]),
+                Definition::Mutation(i) => self.print_mutation(&document.mutations[i]),
+                Definition::Subscription(i) => self.print_subscription(&document.subscriptions[i]),
+                Definition::Fragment(i) => self.print_fragment(&document.fragments[i]),
+                Definition::Schema(i) => self.print_schema_definition(&document.schemas[i]),
+                Definition::Type(i) => self.print_type_definition(&document.types[i]),
+                Definition::Directive(i) => self.print_directive_definition(&document.directives[i]),
+            }
+        }
+    }
+
+    fn print_operation(&mut self, kind: &str, comments: &Vec<&str>, name: Option<&str>, args: &Vec<ArgumentDef>, directives: &Vec<Directive>, fields: &Vec<Field>) {
+        self.print_comments(comments);
+        self.out += kind;
+        if let Some(name) = name {
+            self.out += " ";
+            self.out += name;
+        }
+        self.print_variable_definitions(args);
+        self.print_directives(directives);
+        self.print_selection_set(fields);
+    }
+
+    pub fn print_query(&mut self, query: &Query) {
+        self.print_operation("query", &query.comments, query.name, &query.args, &query.directives, &query.fields);
+    }
+
+    pub fn print_mutation(&mut self, mutation: &Mutation) {
+        self.print_operation("mutation", &mutation.comments, mutation.name, &mutation.args, &mutation.directives, &mutation.fields);
+    }
+
+    pub fn print_subscription(&mut self, subscription: &Subscription) {
+        self.print_operation("subscription", &subscription.comments, subscription.name, &subscription.args, &subscription.directives, &subscription.fields);
+    }
+
+    pub fn print_fragment(&mut self, fragment: &Fragment) {
+        self.print_comments(&fragment.comments);
+        self.out += "fragment ";
+        self.out += fragment.name;
+        self.out += " on ";
+        self.print_type(&fragment.on);
+        self.print_variable_definitions(&fragment.args);
+        self.print_directives(&fragment.directives);
+        self.print_selection_set(&fragment.fields);
+    }
+
+    pub fn print_selection_set(&mut self, fields: &Vec<Field>) {
+        if fields.is_empty() { return }
+
+        self.open_block();
+        for field in fields {
+            self.newline();
+            self.print_field(field);
+        }
+        self.close_block();
+    }
+
+    pub fn print_field(&mut self, field: &Field) {
+        match field {
+            Field::PlainField(field) => {
+                self.print_comments(&field.comments);
+                self.print_plain_field_head(field);
+                self.print_selection_set(&field.fields);
+            },
+            Field::InlineFragment(inline) => {
+                self.print_inline_fragment_head(inline);
+                self.print_selection_set(&inline.fields);
+            },
+            Field::Fragment(spread) => self.print_fragment_spread(spread),
+        }
+    }
+
+    //everything of the field but its selection set
+    pub fn print_plain_field_head(&mut self, field: &PlainField) {
+        if let Some(alias) = field.alias {
+            self.out += alias;
+            self.out += ": ";
+        }
+        self.out += field.name;
+        self.print_arguments(&field.args);
+        self.print_directives(&field.directives);
+    }
+
+    pub fn print_inline_fragment_head(&mut self, inline: &InlineFragment) {
+        self.out += "...";
+        if let Some(on) = &inline.on {
+            self.out += " on ";
+            self.print_type(on);
+        }
+        self.print_directives(&inline.directives);
+    }
+
+    pub fn print_fragment_spread(&mut self, spread: &FragmentSpread) {
+        self.out += "...";
+        self.out += spread.name;
+        self.print_directives(&spread.directives);
+    }
+
+    pub fn print_variable_definitions(&mut self, args: &Vec<ArgumentDef>) {
+        self.print_list("(", ")", args, |printer, arg| {
+            printer.out += "$";
+            printer.out += arg.name;
+            printer.out += ": ";
+            printer.print_type(&arg.kind);
+            printer.print_default_value(&arg.default);
+            printer.print_directives(&arg.directives);
+        });
+    }
+
+    fn print_default_value(&mut self, default: &Option<Value>) {
+        if let Some(default) = default {
+            self.out += " = ";
+            self.print_value(default);
+        }
+    }
+
+    pub fn print_arguments(&mut self, args: &Vec<Argument>) {
+        self.print_list("(", ")", args, Self::print_argument);
+    }
+
+    fn print_argument(&mut self, arg: &Argument) {
+        self.out += arg.name;
+        self.out += ": ";
+        self.print_value(&arg.value);
+    }
+
+    pub fn print_directives(&mut self, directives: &Vec<Directive>) {
+        for directive in directives {
+            self.out += " @";
+            self.out += directive.name;
+            self.print_arguments(&directive.args);
+        }
+    }
+
+    fn print_string(&mut self, s: &str) {
+        self.out += "\"";
+        for c in s.chars() {
+            match c {
+                '"' => self.out += "\\\"",
+                '\\' => self.out += "\\\\",
+                '\n' => self.out += "\\n",
+                '\r' => self.out += "\\r",
+                '\t' => self.out += "\\t",
+                '\u{8}' => self.out += "\\b",
+                '\u{c}' => self.out += "\\f",
+                c if c.is_control() => self.out += &format!("\\u{:04X}", c as u32),
+                c => self.out.push(c),
+            }
+        }
+        self.out += "\"";
+    }
+
+    fn print_block_string(&mut self, s: &str) {
+        let escaped = s.replace("\"\"\"", "\\\"\"\"");
+        //leading whitespace or blank lines would be stripped when lexing the block string again
+        if lexer::block_string_value(&escaped) == escaped.as_str() && !escaped.ends_with('"') {
+            self.out += "\"\"\"";
+            self.out += &escaped;
+            self.out += "\"\"\"";
+        } else {
+            self.print_string(s);
+        }
+    }
+
+    pub fn print_value(&mut self, value: &Value) {
+        match value {
+            Value::Bool(b) => self.out += if *b { "true" } else { "false" },
+            Value::String(s) => self.print_string(s),
+            Value::BlockString(s) => self.print_block_string(s),
+            Value::Int(i) => self.out += &i.to_string(),
+            Value::Float(f) => self.out += &format!("{:?}", f),
+            Value::Null => self.out += "null",
+            Value::Enum(name) => self.out += name,
+            Value::List(values) => {
+                if values.is_empty() { return self.out += "[]" }
+                self.print_list("[", "]", values, Self::print_value)
+            },
+            Value::Object(fields) => {
+                if fields.is_empty() { return self.out += "{}" }
+                self.print_list("{", "}", fields, Self::print_argument)
+            },
+            Value::Variable(name) => {
+                self.out += "$";
+                self.out += name;
+            }
+        }
+    }
+
+    pub fn print_type(&mut self, of_type: &Type) {
+        match of_type {
+            Type::String => self.out += "String",
+            Type::Float => self.out += "Float",
+            Type::Bool => self.out += "Bool",
+            Type::Int => self.out += "Int",
+            Type::NonNull(elem) => {
+                self.print_type(elem);
+                self.out += "!";
+            },
+            Type::Array(elem) => {
+                self.out += "[";
+                self.print_type(elem);
+                self.out += "]";
+            },
+            Type::Input(name) => self.out += name,
+        }
+    }
+
+    fn print_description(&mut self, description: &Option<std::borrow::Cow<str>>) {
+        if let Some(description) = description {
+            if description.contains('\n') {
+                self.print_block_string(description);
+            } else {
+                self.print_string(description);
+            }
+            self.newline();
+        }
+    }
+
+    fn print_extend(&mut self, extension: bool) {
+        if extension { self.out += "extend "; }
+    }
+
+    fn print_block<T, F: Fn(&mut Printer, &T)>(&mut self, items: &Vec<T>, print: F) {
+        if items.is_empty() { return }
+
+        self.open_block();
+        for item in items {
+            self.newline();
+            print(self, item);
+        }
+        self.close_block();
+    }
+
+    pub fn print_schema_definition(&mut self, schema: &SchemaDefinition) {
+        self.print_description(&schema.description);
+        self.print_extend(schema.extension);
+        self.out += "schema";
+        self.print_directives(&schema.directives);
+        self.print_block(&schema.operations, |printer, operation| {
+            printer.out += operation.operation;
+            printer.out += ": ";
+            printer.out += operation.type_name;
+        });
+    }
+
+    fn print_input_value_definition(&mut self, input: &InputValueDefinition) {
+        self.print_description(&input.description);
+        self.out += input.name;
+        self.out += ": ";
+        self.print_type(&input.kind);
+        self.print_default_value(&input.default);
+        self.print_directives(&input.directives);
+    }
+
+    fn print_arguments_definition(&mut self, args: &Vec<InputValueDefinition>) {
+        //descriptions need a line of their own
+        if args.iter().any(|arg| arg.description.is_some()) {
+            self.out += "(";
+            self.level += 1;
+            for arg in args {
+                self.newline();
+                self.print_input_value_definition(arg);
+            }
+            self.level -= 1;
+            self.newline();
+            self.out += ")";
+        } else {
+            self.print_list("(", ")", args, Self::print_input_value_definition);
+        }
+    }
+
+    fn print_field_definition(&mut self, field: &FieldDefinition) {
+        self.print_description(&field.description);
+        self.out += field.name;
+        self.print_arguments_definition(&field.args);
+        self.out += ": ";
+        self.print_type(&field.of_type);
+        self.print_directives(&field.directives);
+    }
+
+    fn print_enum_value_definition(&mut self, value: &EnumValueDefinition) {
+        self.print_description(&value.description);
+        self.out += value.name;
+        self.print_directives(&value.directives);
+    }
+
+    fn print_implements(&mut self, interfaces: &Vec<&str>) {
+        if !interfaces.is_empty() {
+            self.out += " implements ";
+            self.out += &interfaces.join(" & ");
+        }
+    }
+
+    pub fn print_type_definition(&mut self, definition: &TypeDefinition) {
+        self.print_description(&definition.description);
+        self.print_extend(definition.extension);
+        self.out += match definition.kind {
+            TypeDefinitionKind::Scalar => "scalar ",
+            TypeDefinitionKind::Object{ .. } => "type ",
+            TypeDefinitionKind::Interface{ .. } => "interface ",
+            TypeDefinitionKind::Union{ .. } => "union ",
+            TypeDefinitionKind::Enum{ .. } => "enum ",
+            TypeDefinitionKind::InputObject{ .. } => "input ",
+        };
+        self.out += definition.name;
+
+        match &definition.kind {
+            TypeDefinitionKind::Object{ interfaces, fields } | TypeDefinitionKind::Interface{ interfaces, fields } => {
+                self.print_implements(interfaces);
+                self.print_directives(&definition.directives);
+                self.print_block(fields, Self::print_field_definition);
+            },
+            TypeDefinitionKind::Union{ members } => {
+                self.print_directives(&definition.directives);
+                if !members.is_empty() {
+                    self.out += " = ";
+                    self.out += &members.join(" | ");
+                }
+            },
+            TypeDefinitionKind::Enum{ values } => {
+                self.print_directives(&definition.directives);
+                self.print_block(values, Self::print_enum_value_definition);
+            },
+            TypeDefinitionKind::InputObject{ fields } => {
+                self.print_directives(&definition.directives);
+                self.print_block(fields, Self::print_input_value_definition);
+            },
+            TypeDefinitionKind::Scalar => self.print_directives(&definition.directives),
+        }
+    }
+
+    pub fn print_directive_definition(&mut self, directive: &DirectiveDefinition) {
+        self.print_description(&directive.description);
+        self.out += "directive @";
+        self.out += directive.name;
+        self.print_arguments_definition(&directive.args);
+        if directive.repeatable { self.out += " repeatable"; }
+        self.out += " on ";
+        self.out += &directive.locations.join(" | ");
+    }
+}
+
+pub fn print(document: &GraphQL, options: PrintOptions) -> String {
+    let mut printer = Printer::new(options);
+    printer.print_document(document);
+    printer.out += "\n";
+    printer.finish()
+}
+
+//formats a .graphql file, every comment is kept next to the token it was next to
+pub fn format(path: &Path, src: &str, options: PrintOptions) -> Result<String, Vec<Diagnostic>> {
+    let (document, errors) = parser::parse(Lexer::new(path, src));
+    if !errors.is_empty() { return Err(errors) }
+
+    let mut printer = Printer::new(options);
+    printer.comments = false;
+    printer.print_document(&document);
+    printer.out += "\n";
+
+    Ok(restore_comments(path, src, &printer.finish(), options))
+}
+
+//tokens the printer writes the same way, it may turn a string into a block string
+fn same_token(a: &TokenKind, b: &TokenKind) -> bool {
+    match (a, b) {
+        (TokenKind::Identifier(a), TokenKind::Identifier(b)) => a == b,
+        (TokenKind::String(_), TokenKind::String(_) | TokenKind::BlockString(_)) => true,
+        (TokenKind::BlockString(_), TokenKind::String(_) | TokenKind::BlockString(_)) => true,
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+//comments in the trivia of the token, and whether each one is on a line of its own
+fn trivia_comments<'a>(token: &Token<'a>, first: bool) -> Vec<(&'a str, bool)> {
+    token.trivia
+        .split(['\n', '\r'])
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let text = text.trim_start_matches([' ', '\t', ',']);
+            text.strip_prefix('#').map(|text| (text, first || line > 0))
+        })
+        .collect()
+}
+
+//end of the printed line the token is on, a line inside a block string doesn't count
+fn line_end(tokens: &Vec<Token>, printed: &str, token: usize) -> usize {
+    let mut position = tokens[token].span.end;
+    loop {
+        let end = printed[position..].find('\n').map_or(printed.len(), |i| position + i);
+        match tokens.iter().find(|token| token.span.start < end && end < token.span.end) {
+            Some(token) => position = token.span.end,
+            None => return end,
+        }
+    }
+}
+
+//start of the printed line the token is on, None when it starts inside a block string
+fn line_start(tokens: &Vec<Token>, printed: &str, token: usize) -> Option<usize> {
+    let start = tokens[token].span.start;
+    let start = printed[..start].rfind('\n').map_or(0, |i| i + 1);
+    if tokens.iter().any(|token| token.span.start < start && start < token.span.end) { None } else { Some(start) }
+}
+
+//the AST only keeps the comments in front of definitions and fields, so the comments of the source are put back
+//into the printed document by matching the tokens of both, tokens the printer drops or adds are skipped
+fn restore_comments(path: &Path, src: &str, printed: &str, options: PrintOptions) -> String {
+    let (source, _) = lexer::lex(path, src);
+    let (tokens, _) = lexer::lex(path, printed);
+
+    //the comments to put in front of each printed token
+    let mut comments = vec![vec![]; tokens.len()];
+    let mut pending = vec![];
+    let mut next = 0;
+    for (i, token) in source.iter().enumerate() {
+        pending.append(&mut trivia_comments(token, i == 0));
+
+        let matches = |token: &Token, printed: usize| tokens.get(printed).is_some_and(|printed| same_token(&token.kind, &printed.kind));
+        if !matches(token, next) && source[i + 1..].iter().take(2).any(|token| matches(token, next)) {
+            continue
+        }
+        let printed = if matches(token, next) {
+            next
+        } else if matches(token, next + 1) {
+            next + 1
+        } else {
+            continue
+        };
+
+        comments[printed].append(&mut pending);
+        next = printed + 1;
+    }
+    if let Some(last) = comments.last_mut() {
+        last.append(&mut pending);
+    }
+
+    let mut insertions = vec![];
+    //a line only gets one comment at its end, the others go above it
+    let mut trailing = HashSet::new();
+    for (token, comments) in comments.iter().enumerate() {
+        for &(comment, own_line) in comments {
+            if token > 0 && !own_line {
+                let end = line_end(&tokens, printed, token - 1);
+                if trailing.insert(end) {
+                    insertions.push((end, format!(" #{}", comment)));
+                    continue
+                }
+            }
+
+            match line_start(&tokens, printed, token) {
+                Some(start) => {
+                    let line = &printed[start..];
+                    let mut indent = line.len() - line.trim_start_matches(' ').len();
+                    //a comment in front of a closing bracket belongs to the block it closes
+                    let closing = matches!(tokens[token].kind, TokenKind::CloseBracket | TokenKind::CloseParen | TokenKind::CloseSquare);
+                    if closing && tokens[token].span.start == start + indent {
+                        indent += options.indent;
+                    }
+                    insertions.push((start, format!("{}#{}\n", " ".repeat(indent), comment)));
+                },
+                None => insertions.push((line_end(&tokens, printed, token), format!(" #{}", comment))),
+            }
+        }
+    }
+
+    //stable, so the comments at the same place stay in source order
+    insertions.sort_by_key(|&(position, _)| position);
+    let mut out = String::new();
+    let mut copied = 0;
+    for (position, text) in insertions {
+        out += &printed[copied..position];
+        out += &text;
+        copied = position;
+    }
+    out += &printed[copied..];
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENTS: &[&str] = &[
+        "query Profile($id: ID!, $size: Int = 32, $tags: [String!] = [\"a\", \"b\"]) @live { user: node(id: $id) { id ... on User { name avatar(size: $size) } ...Extra } }",
+        "{ viewer { id } }",
+        "mutation { like(input: {id: 1, nested: {list: [1, 2.5, -3e4], flag: true, none: null, kind: RED}}) }",
+        "subscription OnMessage($room: Int!) { messageAdded(room: $room) @include(if: true) { id } }",
+        "fragment Extra on User @skip(if: $hide) { friends(first: 10) { edges { node { id } } } ... @include(if: $more) { bio } }",
+        "query Strings { a(s: \"quote \\\" slash \\\\ tab \\t unicode \\u00e9 control \\u0001\") b(s: \"\"\"block\n  with \\\"\"\" inside\n    and indentation\"\"\") }",
+        "\"\"\"\nThe root\n\"\"\"\nschema @a { query: Query mutation: Mutation }",
+        "extend schema { subscription: Subscription }",
+        "\"a type\" type Node implements & Entity & Named @key(fields: \"id\") { \"the id\" id: ID! items(first: Int = 10, after: String): [Item!]! @deprecated(reason: \"no\") }",
+        "extend type Node { extra: Int }",
+        "interface Named implements Entity { name: String }",
+        "union Result = | Node | Error",
+        "enum Color { RED @deprecated GREEN \"blue\" BLUE }",
+        "input Filter { name: String = \"x\", colors: [Color!] = [RED] }",
+        "scalar Date @specifiedBy(url: \"https://example.com\")",
+        "directive @key(fields: String!) repeatable on OBJECT | INTERFACE",
+        "query Long($aVeryLongVariableName: String, $anotherVeryLongVariableName: String, $third: Int) { field(first: $aVeryLongVariableName, second: $anotherVeryLongVariableName) }",
+    ];
+
+    fn parse(src: &str) -> GraphQL<'_> {
+        let (document, errors) = parser::parse(Lexer::new(Path::new("test.graphql"), src));
+        assert!(errors.is_empty(), "{}: {}", src, errors[0]);
+        document
+    }
+
+    #[test]
+    fn printing_round_trips() {
+        for src in DOCUMENTS {
+            //the printer writes every part of the AST, so the same text means the same AST
+            let printed = print(&parse(src), PrintOptions::default());
+            let reprinted = print(&parse(&printed), PrintOptions::default());
+            assert_eq!(printed, reprinted, "{}", src);
+        }
+    }
+
+    #[test]
+    fn printing_keeps_values() {
+        let printed = print(&parse(DOCUMENTS[5]), PrintOptions::default());
+        let (original, reparsed) = (parse(DOCUMENTS[5]), parse(&printed));
+        let values = |document: &GraphQL| -> Vec<String> {
+            document.queries[0].fields.iter().map(|field| match field {
+                Field::PlainField(field) => match &field.args[0].value {
+                    Value::String(s) | Value::BlockString(s) => s.to_string(),
+                    _ => panic!("not a string"),
+                },
+                _ => panic!("not a plain field"),
+            }).collect()
+        };
+        assert_eq!(values(&original), values(&reparsed));
+    }
+
+    #[test]
+    fn prints_in_source_order() {
+        let printed = print(&parse("fragment F on User { name } query Q { ...F } type T { a: Int } mutation M { a }"), PrintOptions::default());
+        assert_eq!(printed, "fragment F on User {\n  name\n}\n\nquery Q {\n  ...F\n}\n\ntype T {\n  a: Int\n}\n\nmutation M {\n  a\n}\n");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for src in DOCUMENTS {
+            let once = format(Path::new("test.graphql"), src, PrintOptions::default()).ok().unwrap();
+            let twice = format(Path::new("test.graphql"), &once, PrintOptions::default()).ok().unwrap();
+            assert_eq!(once, twice, "{}", src);
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_comments() {
+        let src = "# head\nquery Q( # variables\n  $a: Int\n) {\n  a(x: 1, # first\n   y: 2)\n  # before b\n  b { c } # after b\n  # end of set\n}\n{ x } # shorthand\n# end of file";
+        let once = format(Path::new("test.graphql"), src, PrintOptions::default()).ok().unwrap();
+        assert_eq!(once, "# head\nquery Q($a: Int) { # variables\n  a(x: 1, y: 2) # first\n  # before b\n  b {\n    c\n  } # after b\n  # end of set\n}\n\nquery {\n  x\n} # shorthand\n# end of file\n");
+
+        let twice = format(Path::new("test.graphql"), &once, PrintOptions::default()).ok().unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn formatting_keeps_comments_next_to_block_strings() {
+        let src = "{ a(s: \"\"\"x\n  y\"\"\" # after the string\n  , t: 1) }\ntype T implements & A & B { # fields\n  f: Int\n}";
+        let once = format(Path::new("test.graphql"), src, PrintOptions::default()).ok().unwrap();
+        assert_eq!(once.matches('#').count(), 2);
+        assert!(once.contains("y\"\"\" # after the string\n"));
+        assert!(once.contains("type T implements A & B { # fields\n"));
+        assert_eq!(format(Path::new("test.graphql"), &once, PrintOptions::default()).ok().unwrap(), once);
+    }
+
+    #[test]
+    fn formatting_reports_syntax_errors() {
+        let errors = format(Path::new("test.graphql"), "query { a(", PrintOptions::default()).err().unwrap();
+        assert!(!errors.is_empty());
+    }
+}
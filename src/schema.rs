@@ -3,8 +3,9 @@ use serde_json::{Value, Map};
 use minreq;
 use std::fs;
 use crate::parser::Type;
-use std::fs::read_to_string;
+use crate::error::Diagnostic;
 use std::io::Write;
+use std::borrow::Cow;
 
 #[derive(PartialEq)]
 pub enum NamedTypeKind {
@@ -25,84 +26,117 @@ pub struct Field {
 pub struct NamedType {
     pub name: String,
     pub kind: NamedTypeKind,
-    pub fields: HashMap<String, Field>
+    pub fields: HashMap<String, Field>,
+    //of an input object, in the order of the schema
+    pub input_fields: Vec<Argument>,
+}
+
+impl NamedType {
+    //__typename can be selected on every type without being in the schema
+    pub fn field_type(&self, name: &str) -> Result<Cow<'_, Type>, Diagnostic> {
+        if name == "__typename" { return Ok(Cow::Owned(Type::NonNull(Box::new(Type::String)))) }
+        match self.fields.get(name) {
+            Some(field) => Ok(Cow::Borrowed(&field.of_type)),
+            None => Err(Diagnostic::error("C001", format!("Type {} has no field {}", self.name, name))),
+        }
+    }
 }
 
 pub struct Schema {
-    mutation_type: String,
+    mutation_type: Option<String>,
     query_type: String,
+    subscription_type: Option<String>,
     types: HashMap<String, NamedType>
 }
 
-fn type_from(of_type: &Map<String, Value>) -> Type {
-    let name = of_type["name"].as_str();
-    let kind = of_type["kind"].as_str().unwrap();
+//the introspection result does not have the expected shape
+fn malformed(what: &str) -> Diagnostic {
+    Diagnostic::error("S002", format!("Malformed introspection result, expecting {}", what))
+}
+
+fn get_object<'v>(object: &'v Map<String, Value>, key: &str) -> Result<&'v Map<String, Value>, Diagnostic> {
+    object.get(key).and_then(|value| value.as_object()).ok_or_else(|| malformed(key))
+}
 
-    match kind {
-        "SCALAR" => match name.unwrap() {
+fn get_str<'v>(object: &'v Map<String, Value>, key: &str) -> Result<&'v str, Diagnostic> {
+    object.get(key).and_then(|value| value.as_str()).ok_or_else(|| malformed(key))
+}
+
+fn type_from(of_type: &Map<String, Value>) -> Result<Type, Diagnostic> {
+    let kind = get_str(of_type, "kind")?;
+
+    Ok(match kind {
+        "SCALAR" => match get_str(of_type, "name")? {
             "Int" => Type::Int,
             "Float" => Type::Float,
             "Bool" => Type::Bool,
             "String" => Type::String,
-            _ => Type::Input(name.unwrap().to_string()),
-        },
-        "NON_NULL" => {
-            let of_type = of_type["ofType"].as_object().unwrap();
-            Type::NonNull(Box::new(type_from(of_type)))
-        },
-        "LIST" => {
-            let of_type = of_type["ofType"].as_object().unwrap();
-            Type::Array(Box::new(type_from(of_type)))
+            name => Type::Input(name.to_string()),
         },
+        "NON_NULL" => Type::NonNull(Box::new(type_from(get_object(of_type, "ofType")?)?)),
+        "LIST" => Type::Array(Box::new(type_from(get_object(of_type, "ofType")?)?)),
         "ENUM" => {
             //let of_type = of_type["ofType"].as_object().unwrap();
             //Type::Enum(Box::new(type_from(of_type)))
-            Type::Input(name.unwrap().to_string())
+            Type::Input(get_str(of_type, "name")?.to_string())
         }
-        "OBJECT" | "INTERFACE" | "INPUT_OBJECT" => Type::Input(name.unwrap().to_string()),
-        _ => panic!("Unknown kind {}", kind),
-    }
+        "OBJECT" | "INTERFACE" | "INPUT_OBJECT" => Type::Input(get_str(of_type, "name")?.to_string()),
+        _ => return Err(Diagnostic::error("S003", format!("Unknown kind {}", kind))),
+    })
 }
 
-fn map_array_object<F: Fn(&Map<String, Value>) -> T , T>(value: &Value, func: F) -> Vec<T> {
-    let data = match value.as_array() {
+fn map_array_object<F: Fn(&Map<String, Value>) -> Result<T, Diagnostic>, T>(value: Option<&Value>, func: F) -> Result<Vec<T>, Diagnostic> {
+    let data = match value.and_then(|value| value.as_array()) {
         Some(data) => data,
-        None => return vec![],
+        None => return Ok(vec![]),
     };
 
     let mut result = Vec::with_capacity(data.len());
     for value in data {
-        let obj = value.as_object().unwrap();
-        result.push(func(obj));
+        let obj = value.as_object().ok_or_else(|| malformed("object"))?;
+        result.push(func(obj)?);
     }
-    result
+    Ok(result)
 }
 
-fn args_from(args: &Value) -> Vec<Argument> {
-    map_array_object(args, |arg| Argument {
-        name: arg["name"].as_str().unwrap().to_string(),
-        of_type: type_from(arg["type"].as_object().unwrap()),
-    })
+fn args_from(args: Option<&Value>) -> Result<Vec<Argument>, Diagnostic> {
+    map_array_object(args, |arg| Ok(Argument {
+        name: get_str(arg, "name")?.to_string(),
+        of_type: type_from(get_object(arg, "type")?)?,
+    }))
+}
+
+//schemas downloaded before the input fields were asked with their types only have their names, those are left out
+fn input_fields_from(fields: Option<&Value>) -> Result<Vec<Argument>, Diagnostic> {
+    let fields = map_array_object(fields, |field| match field.get("type") {
+        Some(_) => Ok(Some(Argument {
+            name: get_str(field, "name")?.to_string(),
+            of_type: type_from(get_object(field, "type")?)?,
+        })),
+        None => Ok(None),
+    })?;
+
+    Ok(fields.into_iter().flatten().collect())
 }
 
 //todo perf
-fn fields_from(fields: &Value) -> HashMap<String, Field> {
-    let fields = map_array_object(fields, |field| Field {
-        name: field["name"].as_str().unwrap().to_string(),
-        args: args_from(&field["args"]),
-        of_type: type_from(&field["type"].as_object().unwrap())
-    });
+fn fields_from(fields: Option<&Value>) -> Result<HashMap<String, Field>, Diagnostic> {
+    let fields = map_array_object(fields, |field| Ok(Field {
+        name: get_str(field, "name")?.to_string(),
+        args: args_from(field.get("args"))?,
+        of_type: type_from(get_object(field, "type")?)?
+    }))?;
 
     let mut result = HashMap::new();
     for field in fields {
         result.insert(field.name.clone(), field);
     }
 
-    result
+    Ok(result)
 }
 
 
-pub fn download_schema(url: &str, output: &str) -> Result<String, String> {
+pub fn download_schema(url: &str, output: &str) -> Result<String, Diagnostic> {
     let get_schema = r#"
 fragment typeFrag on __Type {
   kind
@@ -153,7 +187,10 @@ query {
         isDeprecated
         deprecationReason
       }
-      inputFields { name }
+      inputFields {
+        name
+        type { ...typeFrag }
+      }
       interfaces { name }
       enumValues { name }
       possibleTypes { name }
@@ -166,79 +203,101 @@ query {
         .with_body(format!("{{ \"query\" : {:?} }}", get_schema))
         .send() {
         Ok(resp) => resp,
-        Err(e) => return Err("Could not connect to server to download schema".to_string()),
+        Err(e) => return Err(Diagnostic::error("S004", "Could not connect to server to download schema").with_note(e.to_string())),
     };
 
     if resp.status_code == 200 {
         let mut f = match fs::File::create(output) {
             Ok(f) => f,
-            Err(_) => return Err("Could not open schema file for writing".to_string())
+            Err(e) => return Err(Diagnostic::error("S005", "Could not open schema file for writing").with_primary(output, None, "").with_note(e.to_string()))
         };
-        if let Err(_) = f.write(resp.as_bytes()) {
-            return Err("Could not write to schema file".to_string());
+        if let Err(e) = f.write(resp.as_bytes()) {
+            return Err(Diagnostic::error("S005", "Could not write to schema file").with_primary(output, None, "").with_note(e.to_string()));
+        }
+        match resp.as_str() {
+            Ok(body) => Ok(body.to_string()),
+            Err(e) => Err(Diagnostic::error("S001", "Schema is not valid UTF-8").with_note(e.to_string()))
         }
-        Ok(resp.as_str().unwrap().to_string())
     } else {
-        Err(format!("Status code {} for getting schema from url", resp.status_code))
+        Err(Diagnostic::error("S006", format!("Status code {} for getting schema from url", resp.status_code)).with_primary(url, None, ""))
     }
 }
 
-pub fn from(src: &str) -> Result<Schema, serde_json::Error> {
-    let json_schema_resp : serde_json::Map<String, serde_json::Value> = serde_json::from_str(src)?;
-    let json_schema = match json_schema_resp["data"].as_object() {
-        Some(data) => data,
-        None => &json_schema_resp
-    }["__schema"].as_object().unwrap();
+pub fn from(src: &str) -> Result<Schema, Diagnostic> {
+    let json_schema_resp : serde_json::Map<String, serde_json::Value> = serde_json::from_str(src).map_err(|error|
+        Diagnostic::error("S001", "Schema is not valid JSON")
+            .with_note(error.to_string())
+    )?;
+    let json_schema = match json_schema_resp.get("data").and_then(|data| data.as_object()) {
+        Some(data) => get_object(data, "__schema")?,
+        None => get_object(&json_schema_resp, "__schema")?
+    };
 
-    let query_type  = json_schema["queryType"].as_object().unwrap();
-    let mutation_type = json_schema["mutationType"].as_object().unwrap();
-    //let subscription_type = json_schema["subscriptionType"].as_object().unwrap();
-    let types  = json_schema["types"].as_array().unwrap();
+    let query_type  = get_object(json_schema, "queryType")?;
+    //null when the server has no mutations or subscriptions
+    let mutation_type = json_schema.get("mutationType").and_then(|of_type| of_type.as_object());
+    let subscription_type = json_schema.get("subscriptionType").and_then(|of_type| of_type.as_object());
+    let types  = json_schema.get("types").and_then(|types| types.as_array()).ok_or_else(|| malformed("types"))?;
 
     let mut types_result = HashMap::new();
 
     for value in types {
-        let of_type = value.as_object().unwrap();
+        let of_type = value.as_object().ok_or_else(|| malformed("type"))?;
 
-        let name = of_type["name"].as_str().unwrap();
-        let kind_str = of_type["kind"].as_str().unwrap();
+        let name = get_str(of_type, "name")?;
+        let kind_str = get_str(of_type, "kind")?;
         let kind = match kind_str {
             "OBJECT" => NamedTypeKind::Object,
             "INTERFACE" => NamedTypeKind::Interface,
             "SCALAR" => NamedTypeKind::Scalar,
             "INPUT_OBJECT" => NamedTypeKind::InputObject,
             "ENUM" => NamedTypeKind::Enum,
-            _ => panic!("expecting object, interface, input object or scalar, not {}", kind_str)
+            _ => return Err(Diagnostic::error("S003", format!("Expecting object, interface, input object, scalar or enum, not {}", kind_str)))
         };
-        let fields = fields_from(&of_type["fields"]);
+        let fields = fields_from(of_type.get("fields"))?;
+        let input_fields = input_fields_from(of_type.get("inputFields"))?;
 
         types_result.insert(name.to_string(), NamedType{
             name: name.to_string(),
             kind,
-            fields: fields
+            fields,
+            input_fields,
         });
     }
 
+    let root_name = |root: Option<&Map<String, Value>>| -> Result<Option<String>, Diagnostic> {
+        match root {
+            Some(root) => Ok(Some(get_str(root, "name")?.to_string())),
+            None => Ok(None)
+        }
+    };
+
     Ok(Schema{
-        query_type: query_type["name"].as_str().unwrap().to_string(),
-        mutation_type: mutation_type["name"].as_str().unwrap().to_string(),
+        query_type: get_str(query_type, "name")?.to_string(),
+        mutation_type: root_name(mutation_type)?,
+        subscription_type: root_name(subscription_type)?,
         types: types_result
     })
 }
 
 impl Schema {
-    pub fn get_named(&self, object_type: &Type) -> &NamedType {
-        match object_type {
-            Type::Input(name) => self.get(name).unwrap(),
-            Type::Array(elem) => self.get_named(elem.as_ref()),
-            Type::NonNull(elem) => self.get_named(elem.as_ref()),
-            _ => panic!("Can only get field on interface, input or object types"),
-        }
+    //the named type behind the lists and non null wrappers, the builtin scalars have no entry in the schema
+    pub fn get_named(&self, object_type: &Type) -> Result<&NamedType, Diagnostic> {
+        let scalar = match object_type {
+            Type::Input(name) => return self.get(name).ok_or_else(|| Diagnostic::error("C002", format!("Unknown type {}", name))),
+            Type::Array(elem) => return self.get_named(elem.as_ref()),
+            Type::NonNull(elem) => return self.get_named(elem.as_ref()),
+            Type::Int => "Int",
+            Type::Float => "Float",
+            Type::String => "String",
+            Type::Bool => "Boolean",
+        };
+        Err(Diagnostic::error("C005", format!("Type {} is a scalar and has no fields", scalar)))
     }
 
-    pub fn get_type_of_field(&self, object_type: &NamedType, name: &str) -> &NamedType {
-        let of_type = &object_type.fields[name].of_type;
-        return self.get_named(of_type);
+    pub fn get_type_of_field(&self, object_type: &NamedType, name: &str) -> Result<&NamedType, Diagnostic> {
+        let of_type = object_type.field_type(name)?;
+        self.get_named(&of_type)
     }
 
     pub fn get(&self, name: &str) -> Option<&NamedType> {
@@ -250,6 +309,10 @@ impl Schema {
     }
 
     pub fn mutation_root(&self) -> Option<&NamedType> {
-        self.types.get(&self.mutation_type)
+        self.mutation_type.as_ref().and_then(|name| self.types.get(name))
+    }
+
+    pub fn subscription_root(&self) -> Option<&NamedType> {
+        self.subscription_type.as_ref().and_then(|name| self.types.get(name))
     }
 }
\ No newline at end of file
@@ -0,0 +1,244 @@
+use crate::lexer::{Token, TokenKind, Span};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntaxKind {
+    Document,
+    Query,
+    Mutation,
+    Subscription,
+    Fragment,
+    VariableDefinitions,
+    VariableDefinition,
+    Type,
+    SelectionSet,
+    Field,
+    FragmentSpread,
+    InlineFragment,
+    Arguments,
+    Argument,
+    Directive,
+    Value,
+    ObjectField,
+    SchemaDefinition,
+    RootOperation,
+    TypeDefinition,
+    FieldDefinition,
+    InputValueDefinition,
+    EnumValueDefinition,
+    DirectiveDefinition,
+    //tokens skipped while recovering from a syntax error, or invalid text found by the lexer
+    Error,
+}
+
+pub struct SyntaxToken<'a> {
+    pub kind: TokenKind<'a>,
+    //whitespace, commas and comments in front of the token
+    pub trivia: &'a str,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(SyntaxToken<'a>),
+}
+
+//concrete syntax tree which keeps every token and all trivia, printing it gives back the source byte for byte
+pub struct SyntaxNode<'a> {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement<'a>>,
+}
+
+impl<'a> SyntaxNode<'a> {
+    pub fn tokens(&self) -> Vec<&SyntaxToken<'a>> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'b>(&'b self, tokens: &mut Vec<&'b SyntaxToken<'a>>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    //range of the tokens of the node, without the leading trivia
+    pub fn span(&self) -> Option<Span> {
+        let tokens = self.tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Some(Span{ start: first.span.start, end: last.span.end }),
+            _ => None
+        }
+    }
+}
+
+impl<'a> fmt::Display for SyntaxNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens() {
+            f.write_str(token.trivia)?;
+            f.write_str(token.text)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct TreeBuilder<'a> {
+    src: &'a str,
+    stack: Vec<SyntaxNode<'a>>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    pub(crate) fn new(src: &'a str) -> TreeBuilder<'a> {
+        TreeBuilder{ src, stack: vec![SyntaxNode{ kind: SyntaxKind::Document, children: vec![] }] }
+    }
+
+    fn current(&mut self) -> &mut SyntaxNode<'a> {
+        self.stack.last_mut().unwrap()
+    }
+
+    pub(crate) fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push(SyntaxNode{ kind, children: vec![] });
+    }
+
+    //position in the current node, used to wrap children in a node once its kind is known
+    pub(crate) fn checkpoint(&mut self) -> usize {
+        self.current().children.len()
+    }
+
+    pub(crate) fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let children = self.current().children.split_off(checkpoint);
+        self.stack.push(SyntaxNode{ kind, children });
+    }
+
+    pub(crate) fn finish_node(&mut self) {
+        if self.stack.len() > 1 {
+            let node = self.stack.pop().unwrap();
+            self.current().children.push(SyntaxElement::Node(node));
+        }
+    }
+
+    //drops the tokens from the offset on, and the nodes left empty, to parse them again
+    pub(crate) fn rewind(&mut self, offset: usize) {
+        fn truncate(node: &mut SyntaxNode, offset: usize) {
+            while let Some(last) = node.children.last_mut() {
+                match last {
+                    SyntaxElement::Token(token) if token.span.start < offset => return,
+                    SyntaxElement::Token(_) => {},
+                    SyntaxElement::Node(child) => {
+                        truncate(child, offset);
+                        if !child.children.is_empty() { return }
+                    },
+                }
+                node.children.pop();
+            }
+        }
+
+        self.finish_to_root();
+        truncate(self.current(), offset);
+    }
+
+    //closes the nodes left open by a syntax error
+    pub(crate) fn finish_to_root(&mut self) {
+        while self.stack.len() > 1 {
+            self.finish_node();
+        }
+    }
+
+    pub(crate) fn token(&mut self, token: &Token<'a>) {
+        let text = &self.src[token.span.start..token.span.end];
+        self.current().children.push(SyntaxElement::Token(SyntaxToken{
+            kind: token.kind.clone(),
+            trivia: token.trivia,
+            text,
+            span: token.span,
+        }));
+    }
+
+    pub(crate) fn finish(mut self) -> SyntaxNode<'a> {
+        self.finish_to_root();
+        self.stack.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser;
+    use std::path::Path;
+
+    fn syntax(src: &str) -> SyntaxNode<'_> {
+        let (_, tree, _) = parser::parse_with_syntax(Lexer::new(Path::new("test.graphql"), src));
+        tree
+    }
+
+    fn assert_round_trip(src: &str) {
+        assert_eq!(syntax(src).to_string(), src);
+    }
+
+    #[test]
+    fn round_trips_trivia() {
+        assert_round_trip("");
+        assert_round_trip("  \n# only a comment\n");
+        assert_round_trip("# doc\nquery Q($a: Int = 1, $b: [String!]!) @d(x: 1) {\r\n  a: b(c: {d: [1, 2.5, \"s\"]}),, # trailing\n\t...F ... on T { e } ... @include(if: $a) { f }\n}\n\n");
+        assert_round_trip("fragment F on T { a }   mutation { b } subscription S { c }\n# end");
+        assert_round_trip("\"\"\"\n  description\n\"\"\"\ntype A implements & B & C @key { f(a: Int = 2): [A!] }\nunion U = | A | B\nextend schema { query: Q }\n");
+    }
+
+    #[test]
+    fn round_trips_bom() {
+        let src = "\u{FEFF}{ a }";
+        assert_round_trip(src);
+        let tree = syntax(src);
+        assert_eq!(tree.tokens()[0].trivia, "\u{FEFF}");
+    }
+
+    #[test]
+    fn round_trips_invalid_text() {
+        assert_round_trip("query Q { a \u{201C}b\u{201D} ? c }");
+        assert_round_trip("{ a(s: \"open\n) 007 123abc .. }");
+        assert_round_trip("query Q { a(x: ) ");
+        assert_round_trip("} } query { a { b { c");
+        assert_round_trip("query A { a { b ?\nquery B { b }\n# end");
+    }
+
+    #[test]
+    fn invalid_text_is_kept_as_error_nodes() {
+        fn errors<'n, 'a>(node: &'n SyntaxNode<'a>, result: &mut Vec<&'n SyntaxNode<'a>>) {
+            if node.kind == SyntaxKind::Error { result.push(node) }
+            for child in node.nodes() { errors(child, result) }
+        }
+
+        let tree = syntax("{ a ? b } query Q { c( } d } ¿");
+        let mut result = vec![];
+        errors(&tree, &mut result);
+        assert_eq!(result.iter().map(|node| node.to_string()).collect::<Vec<_>>(), vec![" ?", " d } ¿", " ¿"]);
+    }
+
+    #[test]
+    fn nodes_and_spans() {
+        let src = "  query Q { a(x: 1) }";
+        let tree = syntax(src);
+        assert_eq!(tree.kind, SyntaxKind::Document);
+
+        let query = tree.nodes().next().unwrap();
+        assert_eq!(query.kind, SyntaxKind::Query);
+        assert_eq!(query.span(), Some(Span{ start: 2, end: src.len() }));
+
+        let field = query.nodes().next().unwrap().nodes().next().unwrap();
+        assert_eq!(field.kind, SyntaxKind::Field);
+        assert_eq!(field.nodes().next().unwrap().kind, SyntaxKind::Arguments);
+        assert_eq!(field.to_string(), " a(x: 1)");
+    }
+}
@@ -0,0 +1,386 @@
+use crate::parser::{
+    GraphQL, Definition, Query, Mutation, Subscription, Fragment, ArgumentDef, Field, PlainField, InlineFragment,
+    FragmentSpread, Directive, Argument, Value, Type, SchemaDefinition, TypeDefinition, TypeDefinitionKind,
+    FieldDefinition, InputValueDefinition, EnumValueDefinition, DirectiveDefinition,
+};
+
+//walks the document AST, every visit_* method defaults to the walk_* function which visits the children,
+//override a method to act on a node and call the walk_* function to keep going into its children
+pub trait Visitor<'a> {
+    fn visit_document(&mut self, document: &GraphQL<'a>) { walk_document(self, document) }
+    fn visit_query(&mut self, query: &Query<'a>) { walk_query(self, query) }
+    fn visit_mutation(&mut self, mutation: &Mutation<'a>) { walk_mutation(self, mutation) }
+    fn visit_subscription(&mut self, subscription: &Subscription<'a>) { walk_subscription(self, subscription) }
+    fn visit_fragment(&mut self, fragment: &Fragment<'a>) { walk_fragment(self, fragment) }
+    fn visit_variable_definition(&mut self, variable: &ArgumentDef<'a>) { walk_variable_definition(self, variable) }
+    fn visit_selection_set(&mut self, fields: &Vec<Field<'a>>) { walk_selection_set(self, fields) }
+    fn visit_field(&mut self, field: &Field<'a>) { walk_field(self, field) }
+    fn visit_plain_field(&mut self, field: &PlainField<'a>) { walk_plain_field(self, field) }
+    fn visit_inline_fragment(&mut self, inline: &InlineFragment<'a>) { walk_inline_fragment(self, inline) }
+    fn visit_fragment_spread(&mut self, spread: &FragmentSpread<'a>) { walk_fragment_spread(self, spread) }
+    fn visit_directive(&mut self, directive: &Directive<'a>) { walk_directive(self, directive) }
+    fn visit_argument(&mut self, argument: &Argument<'a>) { walk_argument(self, argument) }
+    fn visit_value(&mut self, value: &Value<'a>) { walk_value(self, value) }
+    fn visit_type(&mut self, _of_type: &Type) {}
+    fn visit_schema_definition(&mut self, schema: &SchemaDefinition<'a>) { walk_schema_definition(self, schema) }
+    fn visit_type_definition(&mut self, definition: &TypeDefinition<'a>) { walk_type_definition(self, definition) }
+    fn visit_field_definition(&mut self, field: &FieldDefinition<'a>) { walk_field_definition(self, field) }
+    fn visit_input_value_definition(&mut self, input: &InputValueDefinition<'a>) { walk_input_value_definition(self, input) }
+    fn visit_enum_value_definition(&mut self, value: &EnumValueDefinition<'a>) { walk_enum_value_definition(self, value) }
+    fn visit_directive_definition(&mut self, directive: &DirectiveDefinition<'a>) { walk_directive_definition(self, directive) }
+}
+
+//the definitions are visited in source order
+pub fn walk_document<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, document: &GraphQL<'a>) {
+    for definition in &document.definitions {
+        match *definition {
+            Definition::Query(i) => visitor.visit_query(&document.queries[i]),
+            Definition::Mutation(i) => visitor.visit_mutation(&document.mutations[i]),
+            Definition::Subscription(i) => visitor.visit_subscription(&document.subscriptions[i]),
+            Definition::Fragment(i) => visitor.visit_fragment(&document.fragments[i]),
+            Definition::Schema(i) => visitor.visit_schema_definition(&document.schemas[i]),
+            Definition::Type(i) => visitor.visit_type_definition(&document.types[i]),
+            Definition::Directive(i) => visitor.visit_directive_definition(&document.directives[i]),
+        }
+    }
+}
+
+pub fn walk_query<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, query: &Query<'a>) {
+    for variable in &query.args { visitor.visit_variable_definition(variable) }
+    for directive in &query.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&query.fields);
+}
+
+pub fn walk_mutation<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, mutation: &Mutation<'a>) {
+    for variable in &mutation.args { visitor.visit_variable_definition(variable) }
+    for directive in &mutation.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mutation.fields);
+}
+
+pub fn walk_subscription<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, subscription: &Subscription<'a>) {
+    for variable in &subscription.args { visitor.visit_variable_definition(variable) }
+    for directive in &subscription.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&subscription.fields);
+}
+
+pub fn walk_fragment<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, fragment: &Fragment<'a>) {
+    for variable in &fragment.args { visitor.visit_variable_definition(variable) }
+    visitor.visit_type(&fragment.on);
+    for directive in &fragment.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&fragment.fields);
+}
+
+pub fn walk_variable_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, variable: &ArgumentDef<'a>) {
+    visitor.visit_type(&variable.kind);
+    if let Some(default) = &variable.default { visitor.visit_value(default) }
+    for directive in &variable.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_selection_set<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, fields: &Vec<Field<'a>>) {
+    for field in fields { visitor.visit_field(field) }
+}
+
+pub fn walk_field<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, field: &Field<'a>) {
+    match field {
+        Field::PlainField(field) => visitor.visit_plain_field(field),
+        Field::InlineFragment(inline) => visitor.visit_inline_fragment(inline),
+        Field::Fragment(spread) => visitor.visit_fragment_spread(spread),
+    }
+}
+
+pub fn walk_plain_field<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, field: &PlainField<'a>) {
+    for argument in &field.args { visitor.visit_argument(argument) }
+    for directive in &field.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&field.fields);
+}
+
+pub fn walk_inline_fragment<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, inline: &InlineFragment<'a>) {
+    if let Some(on) = &inline.on { visitor.visit_type(on) }
+    for directive in &inline.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&inline.fields);
+}
+
+pub fn walk_fragment_spread<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, spread: &FragmentSpread<'a>) {
+    for directive in &spread.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_directive<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, directive: &Directive<'a>) {
+    for argument in &directive.args { visitor.visit_argument(argument) }
+}
+
+pub fn walk_argument<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, argument: &Argument<'a>) {
+    visitor.visit_value(&argument.value);
+}
+
+pub fn walk_value<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, value: &Value<'a>) {
+    match value {
+        Value::List(values) => for value in values { visitor.visit_value(value) },
+        Value::Object(fields) => for field in fields { visitor.visit_argument(field) },
+        _ => {}
+    }
+}
+
+pub fn walk_schema_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, schema: &SchemaDefinition<'a>) {
+    for directive in &schema.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_type_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, definition: &TypeDefinition<'a>) {
+    for directive in &definition.directives { visitor.visit_directive(directive) }
+    match &definition.kind {
+        TypeDefinitionKind::Object{ fields, .. } | TypeDefinitionKind::Interface{ fields, .. } =>
+            for field in fields { visitor.visit_field_definition(field) },
+        TypeDefinitionKind::Enum{ values } => for value in values { visitor.visit_enum_value_definition(value) },
+        TypeDefinitionKind::InputObject{ fields } => for field in fields { visitor.visit_input_value_definition(field) },
+        TypeDefinitionKind::Scalar | TypeDefinitionKind::Union{ .. } => {}
+    }
+}
+
+pub fn walk_field_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, field: &FieldDefinition<'a>) {
+    for arg in &field.args { visitor.visit_input_value_definition(arg) }
+    visitor.visit_type(&field.of_type);
+    for directive in &field.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_input_value_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, input: &InputValueDefinition<'a>) {
+    visitor.visit_type(&input.kind);
+    if let Some(default) = &input.default { visitor.visit_value(default) }
+    for directive in &input.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_enum_value_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, value: &EnumValueDefinition<'a>) {
+    for directive in &value.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_directive_definition<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, directive: &DirectiveDefinition<'a>) {
+    for arg in &directive.args { visitor.visit_input_value_definition(arg) }
+}
+
+//same as Visitor but can change the nodes it visits
+pub trait VisitorMut<'a> {
+    fn visit_document(&mut self, document: &mut GraphQL<'a>) { walk_document_mut(self, document) }
+    fn visit_query(&mut self, query: &mut Query<'a>) { walk_query_mut(self, query) }
+    fn visit_mutation(&mut self, mutation: &mut Mutation<'a>) { walk_mutation_mut(self, mutation) }
+    fn visit_subscription(&mut self, subscription: &mut Subscription<'a>) { walk_subscription_mut(self, subscription) }
+    fn visit_fragment(&mut self, fragment: &mut Fragment<'a>) { walk_fragment_mut(self, fragment) }
+    fn visit_variable_definition(&mut self, variable: &mut ArgumentDef<'a>) { walk_variable_definition_mut(self, variable) }
+    fn visit_selection_set(&mut self, fields: &mut Vec<Field<'a>>) { walk_selection_set_mut(self, fields) }
+    fn visit_field(&mut self, field: &mut Field<'a>) { walk_field_mut(self, field) }
+    fn visit_plain_field(&mut self, field: &mut PlainField<'a>) { walk_plain_field_mut(self, field) }
+    fn visit_inline_fragment(&mut self, inline: &mut InlineFragment<'a>) { walk_inline_fragment_mut(self, inline) }
+    fn visit_fragment_spread(&mut self, spread: &mut FragmentSpread<'a>) { walk_fragment_spread_mut(self, spread) }
+    fn visit_directive(&mut self, directive: &mut Directive<'a>) { walk_directive_mut(self, directive) }
+    fn visit_argument(&mut self, argument: &mut Argument<'a>) { walk_argument_mut(self, argument) }
+    fn visit_value(&mut self, value: &mut Value<'a>) { walk_value_mut(self, value) }
+    fn visit_type(&mut self, _of_type: &mut Type) {}
+    fn visit_schema_definition(&mut self, schema: &mut SchemaDefinition<'a>) { walk_schema_definition_mut(self, schema) }
+    fn visit_type_definition(&mut self, definition: &mut TypeDefinition<'a>) { walk_type_definition_mut(self, definition) }
+    fn visit_field_definition(&mut self, field: &mut FieldDefinition<'a>) { walk_field_definition_mut(self, field) }
+    fn visit_input_value_definition(&mut self, input: &mut InputValueDefinition<'a>) { walk_input_value_definition_mut(self, input) }
+    fn visit_enum_value_definition(&mut self, value: &mut EnumValueDefinition<'a>) { walk_enum_value_definition_mut(self, value) }
+    fn visit_directive_definition(&mut self, directive: &mut DirectiveDefinition<'a>) { walk_directive_definition_mut(self, directive) }
+}
+
+pub fn walk_document_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, document: &mut GraphQL<'a>) {
+    for definition in &document.definitions {
+        match *definition {
+            Definition::Query(i) => visitor.visit_query(&mut document.queries[i]),
+            Definition::Mutation(i) => visitor.visit_mutation(&mut document.mutations[i]),
+            Definition::Subscription(i) => visitor.visit_subscription(&mut document.subscriptions[i]),
+            Definition::Fragment(i) => visitor.visit_fragment(&mut document.fragments[i]),
+            Definition::Schema(i) => visitor.visit_schema_definition(&mut document.schemas[i]),
+            Definition::Type(i) => visitor.visit_type_definition(&mut document.types[i]),
+            Definition::Directive(i) => visitor.visit_directive_definition(&mut document.directives[i]),
+        }
+    }
+}
+
+pub fn walk_query_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, query: &mut Query<'a>) {
+    for variable in &mut query.args { visitor.visit_variable_definition(variable) }
+    for directive in &mut query.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mut query.fields);
+}
+
+pub fn walk_mutation_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, mutation: &mut Mutation<'a>) {
+    for variable in &mut mutation.args { visitor.visit_variable_definition(variable) }
+    for directive in &mut mutation.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mut mutation.fields);
+}
+
+pub fn walk_subscription_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, subscription: &mut Subscription<'a>) {
+    for variable in &mut subscription.args { visitor.visit_variable_definition(variable) }
+    for directive in &mut subscription.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mut subscription.fields);
+}
+
+pub fn walk_fragment_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, fragment: &mut Fragment<'a>) {
+    for variable in &mut fragment.args { visitor.visit_variable_definition(variable) }
+    visitor.visit_type(&mut fragment.on);
+    for directive in &mut fragment.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mut fragment.fields);
+}
+
+pub fn walk_variable_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, variable: &mut ArgumentDef<'a>) {
+    visitor.visit_type(&mut variable.kind);
+    if let Some(default) = &mut variable.default { visitor.visit_value(default) }
+    for directive in &mut variable.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_selection_set_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, fields: &mut Vec<Field<'a>>) {
+    for field in fields { visitor.visit_field(field) }
+}
+
+pub fn walk_field_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, field: &mut Field<'a>) {
+    match field {
+        Field::PlainField(field) => visitor.visit_plain_field(field),
+        Field::InlineFragment(inline) => visitor.visit_inline_fragment(inline),
+        Field::Fragment(spread) => visitor.visit_fragment_spread(spread),
+    }
+}
+
+pub fn walk_plain_field_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, field: &mut PlainField<'a>) {
+    for argument in &mut field.args { visitor.visit_argument(argument) }
+    for directive in &mut field.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mut field.fields);
+}
+
+pub fn walk_inline_fragment_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, inline: &mut InlineFragment<'a>) {
+    if let Some(on) = &mut inline.on { visitor.visit_type(on) }
+    for directive in &mut inline.directives { visitor.visit_directive(directive) }
+    visitor.visit_selection_set(&mut inline.fields);
+}
+
+pub fn walk_fragment_spread_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, spread: &mut FragmentSpread<'a>) {
+    for directive in &mut spread.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_directive_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, directive: &mut Directive<'a>) {
+    for argument in &mut directive.args { visitor.visit_argument(argument) }
+}
+
+pub fn walk_argument_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, argument: &mut Argument<'a>) {
+    visitor.visit_value(&mut argument.value);
+}
+
+pub fn walk_value_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, value: &mut Value<'a>) {
+    match value {
+        Value::List(values) => for value in values { visitor.visit_value(value) },
+        Value::Object(fields) => for field in fields { visitor.visit_argument(field) },
+        _ => {}
+    }
+}
+
+pub fn walk_schema_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, schema: &mut SchemaDefinition<'a>) {
+    for directive in &mut schema.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_type_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, definition: &mut TypeDefinition<'a>) {
+    for directive in &mut definition.directives { visitor.visit_directive(directive) }
+    match &mut definition.kind {
+        TypeDefinitionKind::Object{ fields, .. } | TypeDefinitionKind::Interface{ fields, .. } =>
+            for field in fields { visitor.visit_field_definition(field) },
+        TypeDefinitionKind::Enum{ values } => for value in values { visitor.visit_enum_value_definition(value) },
+        TypeDefinitionKind::InputObject{ fields } => for field in fields { visitor.visit_input_value_definition(field) },
+        TypeDefinitionKind::Scalar | TypeDefinitionKind::Union{ .. } => {}
+    }
+}
+
+pub fn walk_field_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, field: &mut FieldDefinition<'a>) {
+    for arg in &mut field.args { visitor.visit_input_value_definition(arg) }
+    visitor.visit_type(&mut field.of_type);
+    for directive in &mut field.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_input_value_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, input: &mut InputValueDefinition<'a>) {
+    visitor.visit_type(&mut input.kind);
+    if let Some(default) = &mut input.default { visitor.visit_value(default) }
+    for directive in &mut input.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_enum_value_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, value: &mut EnumValueDefinition<'a>) {
+    for directive in &mut value.directives { visitor.visit_directive(directive) }
+}
+
+pub fn walk_directive_definition_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, directive: &mut DirectiveDefinition<'a>) {
+    for arg in &mut directive.args { visitor.visit_input_value_definition(arg) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser;
+    use crate::printer::{self, PrintOptions};
+    use std::path::Path;
+
+    fn parse(src: &str) -> GraphQL<'_> {
+        let (document, errors) = parser::parse(Lexer::new(Path::new("test.graphql"), src));
+        assert!(errors.is_empty());
+        document
+    }
+
+    //the name of every definition and field in the order they are visited
+    struct Order(Vec<String>);
+
+    impl<'a> Visitor<'a> for Order {
+        fn visit_query(&mut self, query: &Query<'a>) {
+            self.0.push(format!("query {}", query.name.unwrap_or("")));
+            walk_query(self, query)
+        }
+        fn visit_mutation(&mut self, mutation: &Mutation<'a>) {
+            self.0.push(format!("mutation {}", mutation.name.unwrap_or("")));
+            walk_mutation(self, mutation)
+        }
+        fn visit_fragment(&mut self, fragment: &Fragment<'a>) {
+            self.0.push(format!("fragment {}", fragment.name));
+            walk_fragment(self, fragment)
+        }
+        fn visit_type_definition(&mut self, definition: &TypeDefinition<'a>) {
+            self.0.push(format!("type {}", definition.name));
+            walk_type_definition(self, definition)
+        }
+        fn visit_plain_field(&mut self, field: &PlainField<'a>) {
+            self.0.push(field.name.to_string());
+            walk_plain_field(self, field)
+        }
+        fn visit_fragment_spread(&mut self, spread: &FragmentSpread<'a>) {
+            self.0.push(format!("...{}", spread.name));
+        }
+        fn visit_value(&mut self, value: &Value<'a>) {
+            if let Value::Variable(name) = value { self.0.push(format!("${}", name)) }
+            walk_value(self, value)
+        }
+    }
+
+    #[test]
+    fn visits_definitions_in_source_order() {
+        let document = parse("fragment F on T { a } query Q($v: Int) { b(x: [$v]) { ...F ... on T { c } } } type T { a: Int } mutation { d } fragment G on T { e }");
+        let mut order = Order(vec![]);
+        order.visit_document(&document);
+        assert_eq!(order.0, vec![
+            "fragment F", "a", "query Q", "b", "$v", "...F", "c", "type T", "mutation ", "d", "fragment G", "e",
+        ]);
+    }
+
+    //renames the fields and drops the @skip directives
+    struct Rename;
+
+    impl<'a> VisitorMut<'a> for Rename {
+        fn visit_plain_field(&mut self, field: &mut PlainField<'a>) {
+            if field.name == "old" { field.name = "new" }
+            field.directives.retain(|directive| directive.name != "skip");
+            walk_plain_field_mut(self, field)
+        }
+        fn visit_value(&mut self, value: &mut Value<'a>) {
+            if let Value::Int(i) = value { *i += 1 }
+            walk_value_mut(self, value)
+        }
+    }
+
+    #[test]
+    fn visitor_mut_changes_the_document() {
+        let mut document = parse("query Q { old(x: [1, {y: 2}]) @skip(if: true) { a old } } fragment F on T { old }");
+        Rename.visit_document(&mut document);
+        let expected = parse("query Q { new(x: [2, {y: 3}]) { a new } } fragment F on T { new }");
+        assert_eq!(printer::print(&document, PrintOptions::default()), printer::print(&expected, PrintOptions::default()));
+    }
+}